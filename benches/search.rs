@@ -0,0 +1,79 @@
+use bex::*;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// A config-like source with long block comments and strings, the hot loops of comment and string skipping.
+fn source() -> Vec<u8> {
+    let mut source = vec![];
+    for index in 0..2000 {
+        source.extend_from_slice(b"/* ");
+        source.extend(std::iter::repeat_n(b'x', 200));
+        source.extend_from_slice(format!(" */\nvalue{index} = \"").as_bytes());
+        source.extend(std::iter::repeat_n(b'y', 100));
+        source.extend_from_slice(b"\";\n");
+    }
+    source.push(b'@');
+    source
+}
+
+fn single_byte(c: &mut Criterion) {
+    let source = source();
+    let mut group = c.benchmark_group("seek byte");
+    group.bench_function("generic", |b| b.iter(|| {
+        let mut lexer = Lexer::from_slice(black_box(&source));
+        lexer.seek_until(b'@').unwrap();
+        lexer.pos()
+    }));
+    group.bench_function("memchr", |b| b.iter(|| {
+        let mut lexer = Lexer::from_slice(black_box(&source));
+        lexer.seek_byte(b'@').unwrap();
+        lexer.pos()
+    }));
+    group.finish();
+}
+
+fn byte_set(c: &mut Criterion) {
+    let source = source();
+    let mut group = c.benchmark_group("seek any byte");
+    group.bench_function("generic", |b| b.iter(|| {
+        let mut lexer = Lexer::from_slice(black_box(&source));
+        lexer.seek_until_any(b"@!#").unwrap();
+        lexer.pos()
+    }));
+    group.bench_function("memchr3", |b| b.iter(|| {
+        let mut lexer = Lexer::from_slice(black_box(&source));
+        lexer.seek_any_byte(b"@!#").unwrap();
+        lexer.pos()
+    }));
+    group.finish();
+}
+
+fn terminators(c: &mut Criterion) {
+    let source = source();
+    let mut group = c.benchmark_group("skip comments and strings");
+    group.bench_function("generic", |b| b.iter(|| {
+        let mut lexer = Lexer::from_slice(black_box(&source));
+        while lexer.peek_seq(b"/*").unwrap() {
+            lexer.get_until_seq(b"*/").unwrap();
+            lexer.seek_until(b'"').unwrap();
+            lexer.step_forward().unwrap();
+            lexer.get_until_seq(b"\";").unwrap();
+            lexer.advance(3).unwrap();
+        }
+        lexer.pos()
+    }));
+    group.bench_function("memmem", |b| b.iter(|| {
+        let mut lexer = Lexer::from_slice(black_box(&source));
+        while lexer.peek_seq(b"/*").unwrap() {
+            lexer.get_until_bytes(b"*/").unwrap();
+            lexer.seek_byte(b'"').unwrap();
+            lexer.step_forward().unwrap();
+            lexer.get_until_bytes(b"\";").unwrap();
+            lexer.advance(3).unwrap();
+        }
+        lexer.pos()
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, single_byte, byte_set, terminators);
+criterion_main!(benches);
@@ -0,0 +1,194 @@
+use std::collections::{BTreeSet, HashMap};
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+/// A pattern of a rule, matched against bytes
+pub enum Pattern {
+    /// Matches exactly the given bytes
+    Literal(Vec<u8>),
+    /// Matches a regular expression over bytes, without Unicode support
+    Regex(String)
+}
+
+impl Pattern {
+    /// Converts the pattern into a regular expression syntax tree
+    fn to_hir(&self) -> Result<Hir, String> {
+        match self {
+            Pattern::Literal(bytes) => Ok(Hir::literal(bytes.clone())),
+            Pattern::Regex(pattern) => regex_syntax::ParserBuilder::new()
+                .unicode(false)
+                .utf8(false)
+                .build()
+                .parse(pattern)
+                .map_err(|e| format!("invalid regex: {e}"))
+        }
+    }
+}
+
+/// A state of a deterministic automaton
+pub struct DfaState {
+    /// The rule matched when the automaton stops in this state, the one declared first if several match
+    pub accept: Option<usize>,
+    /// Inclusive byte ranges and the states they lead to, bytes without a range stop the automaton
+    pub transitions: Vec<(u8, u8, usize)>
+}
+
+/// A deterministic automaton matching any of a list of rules, starting in state 0
+pub struct Dfa {
+    pub states: Vec<DfaState>
+}
+
+#[derive(Default)]
+struct NfaState {
+    epsilon:     Vec<usize>,
+    transitions: Vec<(u8, u8, usize)>,
+    accept:      Option<usize>
+}
+
+#[derive(Default)]
+struct Nfa {
+    states: Vec<NfaState>
+}
+
+impl Nfa {
+    fn add(&mut self) -> usize {
+        self.states.push(NfaState::default());
+        self.states.len() - 1
+    }
+
+    fn epsilon(&mut self, from: usize, to: usize) { self.states[from].epsilon.push(to) }
+
+    /// Adds the states matching `hir` after `from`, returning the state reached at the end of a match.
+    fn build(&mut self, hir: &Hir, from: usize) -> Result<usize, String> {
+        match hir.kind() {
+            HirKind::Empty => Ok(from),
+            HirKind::Literal(literal) => {
+                let mut current = from;
+                for byte in literal.0.iter() {
+                    let next = self.add();
+                    self.states[current].transitions.push((*byte, *byte, next));
+                    current = next;
+                }
+                Ok(current)
+            }
+            HirKind::Class(class) => {
+                let next = self.add();
+                let ranges: Vec<(u8, u8)> = match class {
+                    Class::Bytes(bytes) => bytes.ranges().iter().map(|it| (it.start(), it.end())).collect(),
+                    Class::Unicode(unicode) => unicode.ranges().iter()
+                        .map(|it| match (u8::try_from(it.start()), u8::try_from(it.end())) {
+                            (Ok(start), Ok(end)) if end.is_ascii() => Ok((start, end)),
+                            _ => Err("non-ASCII characters are not supported, use byte escapes such as \\xFF".to_string())
+                        })
+                        .collect::<Result<_, _>>()?
+                };
+                for (start, end) in ranges {
+                    self.states[from].transitions.push((start, end, next));
+                }
+                Ok(next)
+            }
+            HirKind::Look(_) => Err("anchors and word boundaries are not supported".to_string()),
+            HirKind::Capture(capture) => self.build(&capture.sub, from),
+            HirKind::Concat(items) => items.iter().try_fold(from, |current, item| self.build(item, current)),
+            HirKind::Alternation(items) => {
+                let end = self.add();
+                for item in items {
+                    let start = self.add();
+                    self.epsilon(from, start);
+                    let last = self.build(item, start)?;
+                    self.epsilon(last, end);
+                }
+                Ok(end)
+            }
+            HirKind::Repetition(repetition) => {
+                let mut current = from;
+                for _ in 0..repetition.min {
+                    current = self.build(&repetition.sub, current)?;
+                }
+                match repetition.max {
+                    None => {
+                        let head = self.add();
+                        self.epsilon(current, head);
+                        let last = self.build(&repetition.sub, head)?;
+                        self.epsilon(last, head);
+                        Ok(head)
+                    }
+                    Some(max) => {
+                        let end = self.add();
+                        self.epsilon(current, end);
+                        for _ in repetition.min..max {
+                            current = self.build(&repetition.sub, current)?;
+                            self.epsilon(current, end);
+                        }
+                        Ok(end)
+                    }
+                }
+            }
+        }
+    }
+
+    fn closure(&self, states: impl IntoIterator<Item = usize>) -> BTreeSet<usize> {
+        let mut closure = BTreeSet::new();
+        let mut pending: Vec<usize> = states.into_iter().collect();
+        while let Some(state) = pending.pop() {
+            if closure.insert(state) {
+                pending.extend(&self.states[state].epsilon);
+            }
+        }
+        closure
+    }
+}
+
+impl Dfa {
+    /// Builds an automaton matching the longest input accepted by any of the patterns
+    ///
+    /// # Arguments
+    /// * `patterns` - The patterns in order of priority, the index of a pattern is its rule
+    ///
+    /// # Returns
+    /// The automaton, or the index of the first invalid pattern and why it is invalid
+    pub fn new(patterns: &[&Pattern]) -> Result<Self, (usize, String)> {
+        let mut nfa = Nfa::default();
+        let start = nfa.add();
+        for (rule, pattern) in patterns.iter().enumerate() {
+            let hir = pattern.to_hir().map_err(|e| (rule, e))?;
+            let from = nfa.add();
+            nfa.epsilon(start, from);
+            let end = nfa.build(&hir, from).map_err(|e| (rule, e))?;
+            if nfa.closure([from]).contains(&end) {
+                return Err((rule, "patterns must not match empty input".to_string()))
+            }
+            nfa.states[end].accept = Some(rule);
+        }
+
+        let mut sets = vec![nfa.closure([start])];
+        let mut ids = HashMap::from([(sets[0].clone(), 0)]);
+        let mut states = vec![];
+        while states.len() < sets.len() {
+            let set = sets[states.len()].clone();
+            let mut targets: Vec<(u8, usize)> = vec![];
+            for byte in 0..=255u8 {
+                let moved = set.iter()
+                    .flat_map(|it| &nfa.states[*it].transitions)
+                    .filter(|(start, end, _)| (*start..=*end).contains(&byte))
+                    .map(|(_, _, to)| *to);
+                let target = nfa.closure(moved);
+                if target.is_empty() { continue }
+                let id = *ids.entry(target.clone()).or_insert_with(|| {
+                    sets.push(target);
+                    sets.len() - 1
+                });
+                targets.push((byte, id));
+            }
+            let mut transitions: Vec<(u8, u8, usize)> = vec![];
+            for (byte, id) in targets {
+                match transitions.last_mut() {
+                    Some((_, end, to)) if *to == id && *end as u16 + 1 == byte as u16 => *end = byte,
+                    _ => transitions.push((byte, byte, id))
+                }
+            }
+            let accept = set.iter().filter_map(|it| nfa.states[*it].accept).min();
+            states.push(DfaState { accept, transitions });
+        }
+        Ok(Self { states })
+    }
+}
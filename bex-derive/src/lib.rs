@@ -0,0 +1,356 @@
+mod dfa;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, ToTokens};
+use syn::parse::ParseStream;
+use syn::{parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Expr, Fields, Ident, LitStr, Type};
+use crate::dfa::{Dfa, Pattern};
+
+/// Implements `bex::Token<u8>` for an enum from patterns on its variants
+///
+/// Every call of `next_token` skips input matched by `#[skip]` patterns and then reads the longest
+/// input matched by any pattern. When patterns match input of the same length, `#[token]` patterns
+/// win over `#[regex]` patterns and otherwise the one declared first wins. All patterns are
+/// compiled into a single DFA at build time.
+///
+/// Attributes on variants, which may be repeated:
+/// * `#[token("class")]` - Matches the exact text.
+/// * `#[regex("[0-9]+")]` - Matches a regular expression over bytes. Unicode classes, anchors and
+///   patterns matching empty input are not supported.
+///
+/// Unit variants are produced as they are. A variant with a single field gets it from the matched
+/// bytes with `From<&[u8]>`, or with a callback given after the pattern such as
+/// `#[regex("[0-9]+", parse_number)]`. The callback is a `fn(&[u8]) -> Option<Field>`, returning
+/// `None` makes `next_token` fail.
+///
+/// Attributes on the enum:
+/// * `#[skip("[ \t\r\n]+")]` - Input matched by the regular expression is skipped. `skip_ignored`
+///   is implemented with these patterns, so the tokenizing loops skip it before each token and
+///   input which is blank or only comments tokenizes to no tokens. `next_token` also skips it when
+///   called on its own.
+/// * `#[scope(Scope)]` - Implements `bex::ScopedToken<u8>` with the given `Scope` type instead.
+///
+/// With `#[scope]`, patterns take `scope = Scope::String` to only apply when that scope is current,
+/// `push = Scope::String` to enter a scope after matching and `pop` to leave the current one.
+/// Patterns without a scope apply whenever the current scope has no patterns of its own.
+///
+/// The error type is `bex::BexError`.
+#[proc_macro_derive(Token, attributes(token, regex, skip, scope))]
+pub fn derive_token(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// What happens after a pattern matched
+enum Action {
+    Skip,
+    Emit { variant: Ident, field: Option<Option<Ident>>, callback: Option<Box<Expr>> }
+}
+
+struct Rule {
+    pattern: Pattern,
+    span:    Span,
+    scope:   Option<Expr>,
+    push:    Option<Expr>,
+    pop:     bool,
+    action:  Action
+}
+
+#[derive(Default)]
+struct Options {
+    scope:    Option<Expr>,
+    push:     Option<Expr>,
+    pop:      bool,
+    callback: Option<Expr>
+}
+
+/// Parses the arguments of a `#[token]`, `#[regex]` or `#[skip]` attribute.
+fn parse_rule(attribute: &Attribute) -> syn::Result<(LitStr, Options)> {
+    attribute.parse_args_with(|input: ParseStream| {
+        let pattern: LitStr = input.parse()?;
+        let mut options = Options::default();
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() { break }
+            let fork = input.fork();
+            let name = fork.parse::<Ident>().ok();
+            if name.is_some() && fork.peek(syn::Token![=]) && !fork.peek(syn::Token![==]) {
+                let name: Ident = input.parse()?;
+                input.parse::<syn::Token![=]>()?;
+                let value: Expr = input.parse()?;
+                match name.to_string().as_str() {
+                    "scope" => options.scope = Some(value),
+                    "push" => options.push = Some(value),
+                    _ => return Err(syn::Error::new(name.span(), "expected `scope`, `push` or `pop`"))
+                }
+            } else if name.is_some_and(|it| it == "pop") && (fork.is_empty() || fork.peek(syn::Token![,])) {
+                input.parse::<Ident>()?;
+                options.pop = true;
+            } else if options.callback.is_none() {
+                options.callback = Some(input.parse()?);
+            } else {
+                return Err(input.error("expected `scope = ...`, `push = ...` or `pop`"))
+            }
+        }
+        Ok((pattern, options))
+    })
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new(input.ident.span(), "`Token` can only be derived for enums"))
+    };
+
+    let mut scope_type: Option<Type> = None;
+    let mut skips = vec![];
+    for attribute in &input.attrs {
+        if attribute.path().is_ident("scope") {
+            scope_type = Some(attribute.parse_args()?);
+        } else if attribute.path().is_ident("skip") {
+            let (pattern, options) = parse_rule(attribute)?;
+            if options.callback.is_some() || options.push.is_some() || options.pop {
+                return Err(syn::Error::new(pattern.span(), "skipped input can only have a `scope`"))
+            }
+            skips.push(Rule {
+                pattern: Pattern::Regex(pattern.value()),
+                span: pattern.span(),
+                scope: options.scope,
+                push: None,
+                pop: false,
+                action: Action::Skip
+            });
+        }
+    }
+
+    let mut tokens = vec![];
+    let mut regexes = vec![];
+    for variant in &data.variants {
+        let field = match &variant.fields {
+            Fields::Unit => None,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Some(None),
+            Fields::Named(fields) if fields.named.len() == 1 => Some(fields.named[0].ident.clone()),
+            _ => None
+        };
+        for attribute in &variant.attrs {
+            let literal = attribute.path().is_ident("token");
+            if !literal && !attribute.path().is_ident("regex") { continue }
+            let (pattern, options) = parse_rule(attribute)?;
+            if field.is_none() && !matches!(variant.fields, Fields::Unit) {
+                return Err(syn::Error::new(variant.ident.span(), "variants with patterns must have at most one field"))
+            }
+            if field.is_none() && options.callback.is_some() {
+                return Err(syn::Error::new(pattern.span(), "a callback needs a field to store its value in"))
+            }
+            let rule = Rule {
+                pattern: match literal {
+                    true => Pattern::Literal(pattern.value().into_bytes()),
+                    false => Pattern::Regex(pattern.value())
+                },
+                span: pattern.span(),
+                scope: options.scope,
+                push: options.push,
+                pop: options.pop,
+                action: Action::Emit { variant: variant.ident.clone(), field: field.clone(), callback: options.callback.map(Box::new) }
+            };
+            match literal {
+                true => tokens.push(rule),
+                false => regexes.push(rule)
+            }
+        }
+    }
+    let rules: Vec<Rule> = tokens.into_iter().chain(regexes).chain(skips).collect();
+    if scope_type.is_none() {
+        if let Some(rule) = rules.iter().find(|it| it.scope.is_some() || it.push.is_some() || it.pop) {
+            return Err(syn::Error::new(rule.span, "scopes need the scope type, add `#[scope(Type)]` to the enum"))
+        }
+    }
+
+    let mut groups: Vec<(Option<&Expr>, Vec<usize>)> = vec![(None, vec![])];
+    for (index, rule) in rules.iter().enumerate() {
+        let key = rule.scope.as_ref().map(|it| it.to_token_stream().to_string());
+        match groups.iter_mut().find(|(scope, _)| scope.map(|it| it.to_token_stream().to_string()) == key) {
+            Some((_, members)) => members.push(index),
+            None => groups.push((rule.scope.as_ref(), vec![index]))
+        }
+    }
+
+    let mut matchers = vec![];
+    for (index, (_, members)) in groups.iter().enumerate() {
+        let patterns: Vec<&Pattern> = members.iter().map(|it| &rules[*it].pattern).collect();
+        let dfa = Dfa::new(&patterns)
+            .map_err(|(rule, message)| syn::Error::new(rules[members[rule]].span, message))?;
+        matchers.push(matcher(&format_ident!("__bex_match_{}", index), &dfa, members));
+    }
+
+    let dispatch = match &scope_type {
+        None => quote! { __bex_match_0(input) },
+        Some(_) => {
+            let branches = groups.iter().enumerate().skip(1).map(|(index, (scope, _))| {
+                let name = format_ident!("__bex_match_{}", index);
+                quote! { if *scope == (#scope) { #name(input) } else }
+            });
+            quote! { #(#branches)* { __bex_match_0(input) } }
+        }
+    };
+    let (scope_argument, scope_value) = match &scope_type {
+        None => (quote! {}, quote! {}),
+        Some(scope) => (quote! { , scope: &#scope }, quote! { , scopes.current() })
+    };
+
+    let skipped: Vec<Literal> = rules.iter().enumerate()
+        .filter(|(_, rule)| matches!(rule.action, Action::Skip))
+        .map(|(index, _)| Literal::usize_unsuffixed(index))
+        .collect();
+    let arms = rules.iter().enumerate().map(|(index, rule)| {
+        let index = Literal::usize_unsuffixed(index);
+        let Action::Emit { variant, field, callback } = &rule.action else {
+            return quote! { #index => continue, }
+        };
+        let push = rule.push.as_ref().map(|it| quote! { scopes.push(#it); });
+        let pop = rule.pop.then(|| quote! { scopes.pop(); });
+        let expected = format!("a valid {variant}");
+        let value = match callback {
+            Some(callback) => quote! {
+                match (#callback)(&lexer.contents()[start..end]) {
+                    ::core::option::Option::Some(value) => value,
+                    ::core::option::Option::None => return Err(::bex::BexError::unexpected(
+                        &::std::string::String::from_utf8_lossy(&lexer.contents()[start..end]),
+                        #expected,
+                        start..end
+                    ))
+                }
+            },
+            None => quote! { ::core::convert::From::from(&lexer.contents()[start..end]) }
+        };
+        let token = match field {
+            None => quote! { Self::#variant },
+            Some(None) => quote! { Self::#variant(#value) },
+            Some(Some(name)) => quote! { Self::#variant { #name: #value } }
+        };
+        quote! { #index => { #pop #push #token } }
+    });
+    let scopes_argument = scope_type.as_ref().map(|_| quote! { , scopes: &mut ::bex::ModeStack<Self::Scope> });
+    let skip_ignored = (!skipped.is_empty()).then(|| quote! {
+        fn skip_ignored(
+            lexer: &mut ::bex::Lexer<'__bex, u8> #scopes_argument
+        ) -> ::core::result::Result<(), Self::Error> {
+            use ::bex::{Analyser as _, SliceAnalyser as _};
+            while let ::core::option::Option::Some((#(#skipped)|*, length)) = lexer.contents().get(lexer.pos()..)
+                .and_then(|input| Self::__bex_match(input #scope_value)) {
+                lexer.advance(length)?;
+            }
+            Ok(())
+        }
+    });
+
+    let body = quote! {
+        use ::bex::{Analyser as _, SliceAnalyser as _};
+        loop {
+            let start = lexer.pos();
+            let Some(&first) = lexer.contents().get(start) else {
+                return Err(::bex::BexError::UnexpectedEof { pos: start })
+            };
+            let Some((rule, length)) = Self::__bex_match(&lexer.contents()[start..] #scope_value) else {
+                return Err(::bex::BexError::unexpected(&(first as char), "a token", start..start + 1))
+            };
+            let end = start + length;
+            lexer.set_pos(end)?;
+            let token = match rule {
+                #(#arms)*
+                _ => unreachable!()
+            };
+            return Ok(token)
+        }
+    };
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    generics.params.insert(0, parse_quote!('__bex));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (own_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let matcher = quote! {
+        #[automatically_derived]
+        impl #own_generics #name #type_generics #where_clause {
+            #[doc(hidden)]
+            fn __bex_match(input: &[u8] #scope_argument) -> ::core::option::Option<(usize, usize)> {
+                #(#matchers)*
+                #dispatch
+            }
+        }
+    };
+    let trait_impl = match &scope_type {
+        None => quote! {
+            #[automatically_derived]
+            impl #impl_generics ::bex::Token<'__bex, u8> for #name #type_generics #where_clause {
+                type Error = ::bex::BexError;
+
+                fn next_token(lexer: &mut ::bex::Lexer<'__bex, u8>) -> ::core::result::Result<Self, Self::Error> {
+                    #body
+                }
+
+                #skip_ignored
+            }
+        },
+        Some(scope) => quote! {
+            #[automatically_derived]
+            impl #impl_generics ::bex::ScopedToken<'__bex, u8> for #name #type_generics #where_clause {
+                type Scope = #scope;
+                type Error = ::bex::BexError;
+
+                fn next_token(
+                    lexer: &mut ::bex::Lexer<'__bex, u8>,
+                    scopes: &mut ::bex::ModeStack<Self::Scope>
+                ) -> ::core::result::Result<Self, Self::Error> {
+                    #body
+                }
+
+                #skip_ignored
+            }
+        }
+    };
+    Ok(quote! {
+        #matcher
+        #trait_impl
+    })
+}
+
+/// Generates a function running the DFA over its input, returning the matched rule and the length of the match.
+fn matcher(name: &Ident, dfa: &Dfa, rules: &[usize]) -> TokenStream2 {
+    if dfa.states.iter().all(|it| it.accept.is_none()) {
+        return quote! {
+            fn #name(_input: &[u8]) -> ::core::option::Option<(usize, usize)> { ::core::option::Option::None }
+        }
+    }
+    let arms = dfa.states.iter().enumerate().flat_map(|(from, state)| {
+        state.transitions.iter().map(move |(start, end, to)| {
+            let from = Literal::usize_unsuffixed(from);
+            let bytes = match start == end {
+                true => Literal::u8_unsuffixed(*start).into_token_stream(),
+                false => {
+                    let (start, end) = (Literal::u8_unsuffixed(*start), Literal::u8_unsuffixed(*end));
+                    quote! { #start..=#end }
+                }
+            };
+            let accept = dfa.states[*to].accept.map(|it| {
+                let rule = Literal::usize_unsuffixed(rules[it]);
+                quote! { matched = ::core::option::Option::Some((#rule, index + 1)); }
+            });
+            let to = Literal::usize_unsuffixed(*to);
+            quote! { (#from, #bytes) => { state = #to; #accept } }
+        })
+    });
+    quote! {
+        fn #name(input: &[u8]) -> ::core::option::Option<(usize, usize)> {
+            let mut state: usize = 0;
+            let mut matched = ::core::option::Option::None;
+            for (index, byte) in input.iter().enumerate() {
+                match (state, *byte) {
+                    #(#arms)*
+                    _ => break
+                }
+            }
+            matched
+        }
+    }
+}
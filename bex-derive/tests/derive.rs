@@ -0,0 +1,117 @@
+use bex::*;
+use bex_derive::Token;
+
+fn text(bytes: &[u8]) -> Option<String> { String::from_utf8(bytes.to_vec()).ok() }
+
+fn number(bytes: &[u8]) -> Option<u8> { std::str::from_utf8(bytes).ok()?.parse().ok() }
+
+#[derive(Debug, PartialEq, Token)]
+#[skip("[ \t\r\n]+")]
+#[skip("//[^\n]*")]
+enum Tok {
+    #[token("class")]
+    Class,
+    #[token("=")]
+    Equals,
+    #[token("==")]
+    EqualsEquals,
+    #[token(";")]
+    Semicolon,
+    #[regex("[a-zA-Z_][a-zA-Z0-9_]*", text)]
+    Ident(String),
+    #[regex("[0-9]+", number)]
+    Number(u8),
+    #[regex("0x[0-9a-fA-F]+")]
+    Hex { digits: Vec<u8> }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Mode {
+    #[default]
+    Code,
+    Str
+}
+
+#[derive(Debug, PartialEq, Token)]
+#[scope(Mode)]
+#[skip("[ \n]+")]
+enum Scoped {
+    #[token("\"", push = Mode::Str)]
+    Quote,
+    #[token("\"", scope = Mode::Str, pop)]
+    EndQuote,
+    #[regex("[^\"]+", text, scope = Mode::Str)]
+    Text(String),
+    #[regex("[a-z]+", text)]
+    Word(String)
+}
+
+fn ident(name: &str) -> Tok { Tok::Ident(name.to_string()) }
+
+#[test]
+fn longest_match_prefers_tokens_on_ties() {
+    let tokens: Vec<Tok> = Lexer::from_slice(b"class classy = == 12 0x1f; // done\n")
+        .tokenize_until_end()
+        .unwrap();
+    assert_eq!(tokens, [
+        Tok::Class,
+        ident("classy"),
+        Tok::Equals,
+        Tok::EqualsEquals,
+        Tok::Number(12),
+        Tok::Hex { digits: b"0x1f".to_vec() },
+        Tok::Semicolon
+    ]);
+}
+
+#[test]
+fn spans_exclude_skipped_input() {
+    let tokens = Lexer::from_slice(b"  a  = b  ").tokenize_spanned::<Tok>().unwrap();
+    let spans: Vec<_> = tokens.iter().map(|it| it.span.clone()).collect();
+    assert_eq!(spans, [2..3, 5..6, 7..8]);
+}
+
+#[test]
+fn errors_carry_their_span() {
+    let error = Lexer::from_slice(b"a $").tokenize_until_end::<Tok>().unwrap_err();
+    assert_eq!(error.span(), Some(2..3));
+    let error = Lexer::from_slice(b"300").tokenize_until_end::<Tok>().unwrap_err();
+    assert!(matches!(error, BexError::Unexpected { ref expected, span, .. } if expected == "a valid Number" && span == (0..3)));
+    let error = <Tok as Token<u8>>::next_token(&mut Lexer::from_slice(b"  ")).unwrap_err();
+    assert!(matches!(error, BexError::UnexpectedEof { pos: 2 }));
+}
+
+#[test]
+fn input_of_only_skipped_patterns_has_no_tokens() {
+    assert_eq!(Lexer::from_slice(b"").tokenize_until_end::<Tok>().unwrap(), []);
+    assert_eq!(Lexer::from_slice(b"  ").tokenize_until_end::<Tok>().unwrap(), []);
+    assert_eq!(Lexer::from_slice(b"// only a comment\n  ").tokenize_until_end::<Tok>().unwrap(), []);
+    assert!(Lexer::from_slice(b" \n ").tokenize_spanned::<Tok>().unwrap().is_empty());
+    assert!(Lexer::from_slice(b" \n ").token_stream::<Tok>().next().is_none());
+    let (tokens, errors) = Lexer::from_slice(b" \n ").tokenize_recovering::<Tok, _>(SkipElement);
+    assert!(tokens.is_empty() && errors.is_empty());
+}
+
+#[test]
+fn scopes_select_rules() {
+    let mut lexer = Lexer::from_slice(b"say \"hi there\" twice");
+    let mut tokens = vec![];
+    while !lexer.is_end() {
+        tokens.push(<Scoped as Token<u8>>::next_token(&mut lexer).unwrap());
+    }
+    assert_eq!(tokens, [
+        Scoped::Word("say".to_string()),
+        Scoped::Quote,
+        Scoped::Text("hi there".to_string()),
+        Scoped::EndQuote,
+        Scoped::Word("twice".to_string())
+    ]);
+    assert!(lexer.modes::<Mode>().is_some_and(ModeStack::is_base));
+}
+
+#[test]
+fn scoped_input_of_only_skipped_patterns_has_no_tokens() {
+    let tokens = Lexer::from_slice(b"hi \n ").tokenize_until_end::<Scoped>().unwrap();
+    assert_eq!(tokens, [Scoped::Word("hi".to_string())]);
+    assert_eq!(Lexer::from_slice(b" \n").tokenize_until_end::<Scoped>().unwrap(), []);
+}
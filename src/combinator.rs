@@ -0,0 +1,496 @@
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::ops;
+use crate::error::BexError;
+use crate::read::{Analyser, Checkpoint};
+
+/// Result type of parsers built from combinators
+pub type ParseResult<O> = std::result::Result<O, ParseError>;
+
+/// A parser failure with the set of things that would have been accepted at its position
+///
+/// Failures of alternatives at the same position are merged, so the expected set lists every
+/// alternative. The failure which got furthest into the input wins otherwise.
+#[derive(Debug)]
+pub struct ParseError {
+    pub pos:      usize,
+    pub expected: Vec<String>,
+    pub found:    Option<String>,
+    /// Committed failures are not backtracked by `alt`, `opt`, `many` and `sep_by`, see `cut`
+    pub cut:      bool,
+    /// The error of the analyser, if it failed for a reason other than a mismatch
+    pub cause:    Option<Box<BexError>>
+}
+
+impl ParseError {
+    /// Creates a failure expecting a single thing
+    ///
+    /// # Arguments
+    /// * `pos` - The position of the failure
+    /// * `expected` - Description of what was expected
+    /// * `found` - Description of what was found, `None` at the end of the input
+    pub fn expected(pos: usize, expected: impl Into<String>, found: Option<String>) -> Self {
+        Self { pos, expected: vec![expected.into()], found, cut: false, cause: None }
+    }
+
+    /// Merges the failures of two alternatives, keeping the one which got further
+    ///
+    /// # Arguments
+    /// * `other` - The failure of the other alternative
+    pub fn merge(mut self, other: ParseError) -> Self {
+        if other.pos > self.pos || self.cause.is_none() && other.cause.is_some() && other.pos == self.pos {
+            return other
+        }
+        if other.pos == self.pos {
+            for expected in other.expected {
+                if !self.expected.contains(&expected) { self.expected.push(expected); }
+            }
+            self.found = self.found.or(other.found);
+            self.cut |= other.cut;
+        }
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(cause) = &self.cause { return fmt::Display::fmt(cause, f) }
+        match self.expected.as_slice() {
+            [] => write!(f, "Unexpected input")?,
+            [expected] => write!(f, "Expected {expected}")?,
+            [init @ .., last] => write!(f, "Expected {} or {last}", init.join(", "))?
+        }
+        match &self.found {
+            Some(found) => write!(f, " but found {found} at offset {}.", self.pos),
+            None => write!(f, " but found the end of the input at offset {}.", self.pos)
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause.as_deref().map(|it| it as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<BexError> for ParseError {
+    fn from(e: BexError) -> Self {
+        let pos = e.span().map_or(0, |it| it.start);
+        Self { pos, expected: vec![], found: None, cut: true, cause: Some(Box::new(e)) }
+    }
+}
+
+impl From<ParseError> for BexError {
+    fn from(e: ParseError) -> Self {
+        match e.cause {
+            Some(cause) => *cause,
+            None => {
+                let expected = match e.expected.as_slice() {
+                    [] => "valid input".to_string(),
+                    [expected] => expected.clone(),
+                    expected => format!("one of {}", expected.join(", "))
+                };
+                let found = e.found.unwrap_or_else(|| "end of input".to_string());
+                BexError::Unexpected { found, expected, span: e.pos..e.pos + 1 }
+            }
+        }
+    }
+}
+
+/// Anything that parses a value from an input, implemented for closures
+///
+/// # Type Parameters
+/// * `A` - The input, any `Analyser` over bytes, characters or tokens.
+/// * `O` - The parsed value.
+pub trait Parser<A: ?Sized, O> {
+    /// Parses a value, leaving the cursor after it on success
+    ///
+    /// # Arguments
+    /// * `input` - The input to parse from
+    fn parse(&mut self, input: &mut A) -> ParseResult<O>;
+
+    /// Parses a value like `parse`, also returning the failure an optional or repeated part
+    /// stopped at, so `seq` and `delimited` can merge it into the failure of the next parser
+    ///
+    /// # Arguments
+    /// * `input` - The input to parse from
+    fn parse_with_pending(&mut self, input: &mut A) -> (ParseResult<O>, Option<ParseError>) { (self.parse(input), None) }
+}
+
+impl<A: ?Sized, O, F: FnMut(&mut A) -> ParseResult<O>> Parser<A, O> for F {
+    fn parse(&mut self, input: &mut A) -> ParseResult<O> { self(input) }
+}
+
+/// Tuples of parsers which are run one after another, see `seq`
+pub trait Sequence<A: ?Sized, O> {
+    /// Runs every parser in order, stopping at the first failure
+    ///
+    /// # Returns
+    /// The values or the first failure, and the failure an optional or repeated part stopped at,
+    /// see `Parser::parse_with_pending`
+    fn parse_sequence(&mut self, input: &mut A) -> (ParseResult<O>, Option<ParseError>);
+}
+
+/// Tuples of parsers which are tried in order, see `alt`
+pub trait Alternatives<A: ?Sized, O> {
+    /// Runs one of the parsers
+    ///
+    /// # Arguments
+    /// * `index` - Index of the parser in the tuple
+    /// * `input` - The input to parse from
+    ///
+    /// # Returns
+    /// `None` if the tuple has no parser at that index, otherwise its result
+    fn parse_nth(&mut self, index: usize, input: &mut A) -> Option<ParseResult<O>>;
+}
+
+macro_rules! tuple_parsers {
+    ($($parser:ident $output:ident $index:tt),+) => {
+        impl<A: ?Sized, $($output,)+ $($parser: Parser<A, $output>),+> Sequence<A, ($($output,)+)> for ($($parser,)+) {
+            fn parse_sequence(&mut self, input: &mut A) -> (ParseResult<($($output,)+)>, Option<ParseError>) {
+                let mut pending = None;
+                let values = ($(
+                    match self.$index.parse_with_pending(input) {
+                        (Ok(value), next) => {
+                            pending = combine_pending(pending, next);
+                            value
+                        }
+                        (Err(error), _) => return (Err(merge_pending(error, pending)), None)
+                    },
+                )+);
+                (Ok(values), pending)
+            }
+        }
+
+        impl<A: ?Sized, O, $($parser: Parser<A, O>),+> Alternatives<A, O> for ($($parser,)+) {
+            fn parse_nth(&mut self, index: usize, input: &mut A) -> Option<ParseResult<O>> {
+                match index {
+                    $($index => Some(self.$index.parse(input)),)+
+                    _ => None
+                }
+            }
+        }
+    };
+}
+
+tuple_parsers!(P0 O0 0, P1 O1 1);
+tuple_parsers!(P0 O0 0, P1 O1 1, P2 O2 2);
+tuple_parsers!(P0 O0 0, P1 O1 1, P2 O2 2, P3 O3 3);
+tuple_parsers!(P0 O0 0, P1 O1 1, P2 O2 2, P3 O3 3, P4 O4 4);
+tuple_parsers!(P0 O0 0, P1 O1 1, P2 O2 2, P3 O3 3, P4 O4 4, P5 O5 5);
+tuple_parsers!(P0 O0 0, P1 O1 1, P2 O2 2, P3 O3 3, P4 O4 4, P5 O5 5, P6 O6 6);
+tuple_parsers!(P0 O0 0, P1 O1 1, P2 O2 2, P3 O3 3, P4 O4 4, P5 O5 5, P6 O6 6, P7 O7 7);
+
+/// Rolls the input back to a checkpoint after a failure which may be backtracked
+///
+/// # Returns
+/// The failure, or the error of the analyser if it cannot move back
+fn backtrack<T: Sized + PartialEq, A: Analyser<T> + ?Sized>(input: &mut A, checkpoint: Checkpoint, error: ParseError) -> ParseError {
+    match input.rollback(checkpoint) {
+        Ok(()) => error,
+        Err(e) => e.into()
+    }
+}
+
+/// Recovers from a failure of an optional parser by rolling the input back.
+///
+/// # Returns
+/// Ok with the backtracked failure, otherwise the failure after `cut` or of the analyser
+fn recover<T: Sized + PartialEq, A: Analyser<T> + ?Sized>(input: &mut A, checkpoint: Checkpoint, error: ParseError) -> ParseResult<ParseError> {
+    if error.cut { return Err(error) }
+    match backtrack(input, checkpoint, error) {
+        error if error.cause.is_some() => Err(error),
+        error => Ok(error)
+    }
+}
+
+/// Merges the failure an optional or repeated parser stopped at into a later failure at the same position.
+fn merge_pending(error: ParseError, pending: Option<ParseError>) -> ParseError {
+    match pending {
+        Some(pending) if pending.pos == error.pos => pending.merge(error),
+        _ => error
+    }
+}
+
+/// Keeps the failures optional or repeated parsers stopped at, merging those at the same position.
+fn combine_pending(pending: Option<ParseError>, next: Option<ParseError>) -> Option<ParseError> {
+    match (pending, next) {
+        (Some(pending), Some(next)) if pending.pos == next.pos => Some(pending.merge(next)),
+        (pending, next) => next.or(pending)
+    }
+}
+
+/// Describes the element at the cursor for `ParseError::found`.
+fn found<T: Sized + PartialEq + Debug, A: Analyser<T> + ?Sized>(input: &A) -> Option<String> {
+    input.peek().ok().map(|it| format!("{it:?}"))
+}
+
+/// Parses a single element equal to the given one
+///
+/// # Arguments
+/// * `expected` - The element to accept, its `Debug` form is used in the expected set
+pub fn token<T: Sized + PartialEq + Clone + Debug, A: Analyser<T> + ?Sized>(expected: T) -> impl FnMut(&mut A) -> ParseResult<T> {
+    let description = format!("{expected:?}");
+    satisfy(description, move |it| *it == expected)
+}
+
+/// Parses a single element accepted by a predicate
+///
+/// # Arguments
+/// * `description` - What the predicate accepts, used in the expected set
+/// * `predicate` - Returns true for elements to accept
+pub fn satisfy<T: Sized + PartialEq + Clone + Debug, A: Analyser<T> + ?Sized, F: FnMut(&T) -> bool>(
+    description: impl Into<String>,
+    mut predicate: F
+) -> impl FnMut(&mut A) -> ParseResult<T> {
+    let description = description.into();
+    move |input| match input.peek() {
+        Ok(element) if predicate(element) => {
+            let element = element.clone();
+            input.step_forward()?;
+            Ok(element)
+        }
+        _ => Err(ParseError::expected(input.pos(), description.clone(), found(input)))
+    }
+}
+
+/// Parses a sequence of elements equal to the given ones, such as a keyword
+///
+/// # Arguments
+/// * `expected` - The elements to accept
+pub fn tag<T: Sized + PartialEq + Clone + Debug, A: Analyser<T> + ?Sized>(expected: &[T]) -> impl FnMut(&mut A) -> ParseResult<Vec<T>> + '_ {
+    move |input| {
+        let start = input.checkpoint();
+        for element in expected {
+            if input.peek().ok() != Some(element) {
+                let error = ParseError::expected(start.pos(), format!("{expected:?}"), found(input));
+                return Err(backtrack(input, start, error))
+            }
+            input.step_forward()?;
+        }
+        Ok(expected.to_vec())
+    }
+}
+
+/// Runs a tuple of parsers one after another
+///
+/// If a parser fails where an optional or repeated parser before it stopped, the failure also
+/// lists what that parser expected, so `seq((many(item), token(x)))` expects an item or `x`.
+///
+/// # Arguments
+/// * `parsers` - Tuple of up to eight parsers
+///
+/// # Returns
+/// A parser of the tuple of their values
+pub fn seq<A: ?Sized, O, P: Sequence<A, O>>(mut parsers: P) -> impl FnMut(&mut A) -> ParseResult<O> {
+    move |input| parsers.parse_sequence(input).0
+}
+
+/// Tries a tuple of parsers in order, each from the same position, until one succeeds
+///
+/// Stops at a failure after `cut`. Otherwise the failure lists what every alternative expected.
+///
+/// # Arguments
+/// * `parsers` - Tuple of up to eight parsers of the same type of value
+pub fn alt<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, P: Alternatives<A, O>>(mut parsers: P) -> impl FnMut(&mut A) -> ParseResult<O> {
+    move |input| {
+        let start = input.checkpoint();
+        let mut failure: Option<ParseError> = None;
+        let mut index = 0;
+        while let Some(result) = parsers.parse_nth(index, input) {
+            let error = match result {
+                Ok(value) => return Ok(value),
+                Err(error) => error
+            };
+            let cut = error.cut;
+            let error = match failure.take() {
+                Some(failure) => failure.merge(error),
+                None => error
+            };
+            if cut { return Err(error) }
+            failure = Some(backtrack(input, start.clone(), error));
+            if failure.as_ref().is_some_and(|it| it.cause.is_some()) { break }
+            index += 1;
+        }
+        Err(failure.unwrap_or_else(|| ParseError::expected(start.pos(), "an alternative", None)))
+    }
+}
+
+/// Runs a parser, producing `None` instead of failing if it fails without `cut`
+///
+/// # Arguments
+/// * `parser` - The optional parser
+pub fn opt<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, P: Parser<A, O>>(parser: P) -> Opt<T, P> {
+    Opt { parser, element: PhantomData }
+}
+
+/// Parser created by `opt`
+pub struct Opt<T, P> {
+    parser:  P,
+    element: PhantomData<fn(T)>
+}
+
+impl<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, P: Parser<A, O>> Parser<A, Option<O>> for Opt<T, P> {
+    fn parse(&mut self, input: &mut A) -> ParseResult<Option<O>> { self.parse_with_pending(input).0 }
+
+    fn parse_with_pending(&mut self, input: &mut A) -> (ParseResult<Option<O>>, Option<ParseError>) {
+        let start = input.checkpoint();
+        match self.parser.parse_with_pending(input) {
+            (Ok(value), pending) => (Ok(Some(value)), pending),
+            (Err(error), _) => match recover(input, start, error) {
+                Ok(error) => (Ok(None), Some(error)),
+                Err(error) => (Err(error), None)
+            }
+        }
+    }
+}
+
+/// Runs a parser as many times as it succeeds, stopping at a failure without `cut`
+///
+/// Also stops if the parser succeeds without consuming anything.
+///
+/// # Arguments
+/// * `parser` - The repeated parser
+pub fn many<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, P: Parser<A, O>>(parser: P) -> Many<T, P> {
+    Many { parser, element: PhantomData }
+}
+
+/// Parser created by `many`
+pub struct Many<T, P> {
+    parser:  P,
+    element: PhantomData<fn(T)>
+}
+
+impl<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, P: Parser<A, O>> Parser<A, Vec<O>> for Many<T, P> {
+    fn parse(&mut self, input: &mut A) -> ParseResult<Vec<O>> { self.parse_with_pending(input).0 }
+
+    fn parse_with_pending(&mut self, input: &mut A) -> (ParseResult<Vec<O>>, Option<ParseError>) {
+        let mut values = vec![];
+        loop {
+            let start = input.checkpoint();
+            match self.parser.parse(input) {
+                Ok(value) => values.push(value),
+                Err(error) => return match recover(input, start, error) {
+                    Ok(error) => (Ok(values), Some(error)),
+                    Err(error) => (Err(error), None)
+                }
+            }
+            if input.pos() == start.pos() { return (Ok(values), None) }
+        }
+    }
+}
+
+/// Parses zero or more items separated by a separator, without a trailing separator
+///
+/// # Arguments
+/// * `item` - The parser of the items
+/// * `separator` - The parser of the separators, whose values are discarded
+pub fn sep_by<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, S, P: Parser<A, O>, Q: Parser<A, S>>(
+    item: P,
+    separator: Q
+) -> SepBy<T, S, P, Q> {
+    SepBy { item, separator, element: PhantomData }
+}
+
+/// Parser created by `sep_by`
+pub struct SepBy<T, S, P, Q> {
+    item:      P,
+    separator: Q,
+    element:   PhantomData<fn(T) -> S>
+}
+
+impl<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, S, P: Parser<A, O>, Q: Parser<A, S>> Parser<A, Vec<O>> for SepBy<T, S, P, Q> {
+    fn parse(&mut self, input: &mut A) -> ParseResult<Vec<O>> { self.parse_with_pending(input).0 }
+
+    fn parse_with_pending(&mut self, input: &mut A) -> (ParseResult<Vec<O>>, Option<ParseError>) {
+        let mut values = vec![];
+        loop {
+            let start = input.checkpoint();
+            let result = match values.is_empty() {
+                true => self.item.parse(input),
+                false => self.separator.parse(input).and_then(|_| self.item.parse(input))
+            };
+            match result {
+                Ok(value) => values.push(value),
+                Err(error) => return match recover(input, start, error) {
+                    Ok(error) => (Ok(values), Some(error)),
+                    Err(error) => (Err(error), None)
+                }
+            }
+        }
+    }
+}
+
+/// Parses a value between an opening and a closing delimiter, such as brackets
+///
+/// # Arguments
+/// * `open` - The parser of the opening delimiter
+/// * `inner` - The parser of the value
+/// * `close` - The parser of the closing delimiter
+pub fn delimited<A: ?Sized, L, O, R, P: Parser<A, L>, Q: Parser<A, O>, S: Parser<A, R>>(
+    mut open: P,
+    mut inner: Q,
+    mut close: S
+) -> impl FnMut(&mut A) -> ParseResult<O> {
+    move |input| {
+        open.parse(input)?;
+        let (value, pending) = inner.parse_with_pending(input);
+        let value = value?;
+        close.parse(input).map_err(|error| merge_pending(error, pending))?;
+        Ok(value)
+    }
+}
+
+/// Transforms the value of a parser
+///
+/// # Arguments
+/// * `parser` - The parser whose value is transformed
+/// * `f` - Function applied to the value
+pub fn map<A: ?Sized, O, U, P: Parser<A, O>, F: FnMut(O) -> U>(mut parser: P, mut f: F) -> impl FnMut(&mut A) -> ParseResult<U> {
+    move |input| parser.parse(input).map(&mut f)
+}
+
+/// Commits to a parser, so its failures are reported instead of backtracked by `alt`, `opt`, `many` and `sep_by`
+///
+/// Used after the part of a construct which identifies it, such as a keyword, so errors point at
+/// the actual problem instead of an earlier alternative.
+///
+/// # Arguments
+/// * `parser` - The parser to commit to
+pub fn cut<A: ?Sized, O, P: Parser<A, O>>(mut parser: P) -> impl FnMut(&mut A) -> ParseResult<O> {
+    move |input| parser.parse(input).map_err(|mut error| {
+        error.cut = true;
+        error
+    })
+}
+
+/// Replaces the expected set of failures at the start of a parser with a single description
+///
+/// # Arguments
+/// * `description` - What the parser accepts, such as `an expression`
+/// * `parser` - The parser to describe
+pub fn label<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, P: Parser<A, O>>(
+    description: impl Into<String>,
+    mut parser: P
+) -> impl FnMut(&mut A) -> ParseResult<O> {
+    let description = description.into();
+    move |input| {
+        let start = input.pos();
+        parser.parse(input).map_err(|mut error| {
+            if error.pos == start && error.cause.is_none() { error.expected = vec![description.clone()]; }
+            error
+        })
+    }
+}
+
+/// Runs a parser and returns the range of the input it consumed along with its value
+///
+/// # Arguments
+/// * `parser` - The parser to run
+pub fn spanned<T: Sized + PartialEq, A: Analyser<T> + ?Sized, O, P: Parser<A, O>>(mut parser: P) -> impl FnMut(&mut A) -> ParseResult<(O, ops::Range<usize>)> {
+    move |input| {
+        let start = input.pos();
+        let value = parser.parse(input)?;
+        Ok((value, start..input.pos()))
+    }
+}
@@ -0,0 +1,612 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::{fmt, iter, ops};
+
+/// The kind of a node or token, usually converted from an enum of the grammar being parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SyntaxKind(pub u16);
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct GreenTokenData {
+    kind: SyntaxKind,
+    text: Box<[u8]>
+}
+
+/// An immutable leaf of a green tree, see `GreenNodeBuilder`
+///
+/// Green tokens know their text but not their position, so equal tokens can be shared.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken(Rc<GreenTokenData>);
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: &[u8]) -> Self { Self(Rc::new(GreenTokenData { kind, text: text.into() })) }
+
+    /// Get the kind of the token
+    pub fn kind(&self) -> SyntaxKind { self.0.kind }
+
+    /// Get the source text of the token
+    pub fn text(&self) -> &[u8] { &self.0.text }
+
+    /// Get the length of the text of the token
+    pub fn text_len(&self) -> usize { self.0.text.len() }
+
+    /// Check if two tokens share the same allocation, as interned identical tokens do
+    pub fn ptr_eq(&self, other: &Self) -> bool { Rc::ptr_eq(&self.0, &other.0) }
+}
+
+impl fmt::Debug for GreenToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {:?}", self.kind(), String::from_utf8_lossy(self.text()))
+    }
+}
+
+/// A child of a green node
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(GreenNode),
+    Token(GreenToken)
+}
+
+impl GreenElement {
+    /// Get the kind of the child
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            Self::Node(node) => node.kind(),
+            Self::Token(token) => token.kind()
+        }
+    }
+
+    /// Get the length of the text of the child
+    pub fn text_len(&self) -> usize {
+        match self {
+            Self::Node(node) => node.text_len(),
+            Self::Token(token) => token.text_len()
+        }
+    }
+
+    /// Identifies the shared allocation of the child, interned children are compared by identity
+    fn id(&self) -> usize {
+        match self {
+            Self::Node(node) => Rc::as_ptr(&node.0) as usize,
+            Self::Token(token) => Rc::as_ptr(&token.0) as usize
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct GreenNodeData {
+    kind:     SyntaxKind,
+    text_len: usize,
+    children: Vec<GreenElement>
+}
+
+/// An immutable inner node of a green tree, see `GreenNodeBuilder`
+///
+/// Green nodes know the total length of their text but not their position or parent, so
+/// identical subtrees can be shared, and are cheap to clone.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode(Rc<GreenNodeData>);
+
+impl GreenNode {
+    /// Creates a node from its children
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of the node
+    /// * `children` - The children of the node, in source order
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let text_len = children.iter().map(GreenElement::text_len).sum();
+        Self(Rc::new(GreenNodeData { kind, text_len, children }))
+    }
+
+    /// Get the kind of the node
+    pub fn kind(&self) -> SyntaxKind { self.0.kind }
+
+    /// Get the length of the text of every token below the node
+    pub fn text_len(&self) -> usize { self.0.text_len }
+
+    /// Get the children of the node
+    pub fn children(&self) -> &[GreenElement] { &self.0.children }
+
+    /// Check if two nodes share the same allocation, as interned identical subtrees do
+    pub fn ptr_eq(&self, other: &Self) -> bool { Rc::ptr_eq(&self.0, &other.0) }
+
+    fn write_text(&self, text: &mut Vec<u8>) {
+        for child in self.children() {
+            match child {
+                GreenElement::Node(node) => node.write_text(text),
+                GreenElement::Token(token) => text.extend_from_slice(token.text())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for GreenNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GreenNode").field("kind", &self.kind()).field("text_len", &self.text_len()).finish_non_exhaustive()
+    }
+}
+
+/// Interns green tokens and small green nodes, so identical ones are stored once
+///
+/// A cache can be shared between builders of many files to deduplicate common subtrees.
+#[derive(Debug, Default)]
+pub struct NodeCache {
+    tokens: HashMap<Box<[u8]>, Vec<GreenToken>>,
+    nodes:  HashMap<(SyntaxKind, Vec<usize>), GreenNode>
+}
+
+impl NodeCache {
+    /// Nodes with more children than this are rarely identical and are not interned
+    const MAX_INTERNED_CHILDREN: usize = 3;
+
+    pub fn new() -> Self { Self::default() }
+
+    /// Get the shared token with the given kind and text
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of the token
+    /// * `text` - The source text of the token
+    pub fn token(&mut self, kind: SyntaxKind, text: &[u8]) -> GreenToken {
+        if let Some(token) = self.tokens.get(text).and_then(|it| it.iter().find(|it| it.kind() == kind)) {
+            return token.clone()
+        }
+        let token = GreenToken::new(kind, text);
+        self.tokens.entry(Box::from(text)).or_default().push(token.clone());
+        token
+    }
+
+    /// Get the shared node with the given kind and children, nodes with many children are not shared
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of the node
+    /// * `children` - The children of the node, which are compared by identity
+    pub fn node(&mut self, kind: SyntaxKind, children: Vec<GreenElement>) -> GreenNode {
+        if children.len() > Self::MAX_INTERNED_CHILDREN {
+            return GreenNode::new(kind, children)
+        }
+        let key = (kind, children.iter().map(GreenElement::id).collect());
+        self.nodes.entry(key).or_insert_with(|| GreenNode::new(kind, children)).clone()
+    }
+}
+
+/// A position in a `GreenNodeBuilder` that a node can later be started at
+///
+/// Used for constructs whose kind is only known after their first child has been built, such as
+/// the left operand of a binary expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuilderCheckpoint(usize);
+
+/// Builds a green tree from the tokens of a parser in source order
+///
+/// Every token, including trivia, must be added for the tree to be lossless.
+#[derive(Debug, Default)]
+pub struct GreenNodeBuilder {
+    cache:    NodeCache,
+    parents:  Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>
+}
+
+impl GreenNodeBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    /// Creates a builder which interns into an existing cache, see `finish_with_cache`
+    ///
+    /// # Arguments
+    /// * `cache` - The cache to intern tokens and nodes into
+    pub fn with_cache(cache: NodeCache) -> Self { Self { cache, ..Self::default() } }
+
+    /// Starts a node, every element added until the matching `finish_node` becomes its child
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of the node
+    pub fn start_node(&mut self, kind: SyntaxKind) { self.parents.push((kind, self.children.len())); }
+
+    /// Adds a token to the current node
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of the token
+    /// * `text` - The source text of the token
+    pub fn token(&mut self, kind: SyntaxKind, text: &[u8]) {
+        let token = self.cache.token(kind, text);
+        self.children.push(GreenElement::Token(token));
+    }
+
+    /// Adds an already built node, such as one reused from a previous tree, to the current node
+    ///
+    /// # Arguments
+    /// * `node` - The node to add
+    pub fn node(&mut self, node: GreenNode) { self.children.push(GreenElement::Node(node)); }
+
+    /// Finishes the current node, panics if no node has been started
+    pub fn finish_node(&mut self) {
+        let (kind, first_child) = self.parents.pop().expect("finish_node called without a started node");
+        let children = self.children.split_off(first_child);
+        let node = self.cache.node(kind, children);
+        self.children.push(GreenElement::Node(node));
+    }
+
+    /// Get a checkpoint at the current position, see `start_node_at`
+    pub fn checkpoint(&self) -> BuilderCheckpoint { BuilderCheckpoint(self.children.len()) }
+
+    /// Starts a node which wraps every element added to the current node since a checkpoint
+    ///
+    /// # Arguments
+    /// * `checkpoint` - A checkpoint taken in the current node
+    /// * `kind` - The kind of the node
+    pub fn start_node_at(&mut self, checkpoint: BuilderCheckpoint, kind: SyntaxKind) {
+        let BuilderCheckpoint(position) = checkpoint;
+        assert!(position <= self.children.len(), "checkpoint is no longer valid");
+        if let Some(&(_, first_child)) = self.parents.last() {
+            assert!(position >= first_child, "checkpoint was taken outside of the current node");
+        }
+        self.parents.push((kind, position));
+    }
+
+    /// Finishes building, every started node must have been finished
+    ///
+    /// # Returns
+    /// The root node, panics unless exactly one root node was built
+    pub fn finish(self) -> GreenNode { self.finish_with_cache().0 }
+
+    /// Finishes building and returns the cache for the next builder
+    ///
+    /// # Returns
+    /// The root node and the cache, panics unless exactly one root node was built
+    pub fn finish_with_cache(mut self) -> (GreenNode, NodeCache) {
+        assert!(self.parents.is_empty(), "every started node must be finished");
+        assert_eq!(self.children.len(), 1, "a tree must have exactly one root node");
+        let root = match self.children.pop() {
+            Some(GreenElement::Node(node)) => node,
+            _ => panic!("the root of a tree must be a node")
+        };
+        (root, self.cache)
+    }
+}
+
+#[derive(Debug)]
+struct SyntaxNodeData {
+    green:  GreenNode,
+    parent: Option<SyntaxNode>,
+    index:  usize,
+    offset: usize
+}
+
+/// A node of a red tree, a view of a green node which knows its parent and absolute offset
+///
+/// Red nodes are created on demand while traversing and are cheap to clone.
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<SyntaxNodeData>);
+
+/// A token of a red tree, which knows its parent and absolute offset
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green:  GreenToken,
+    parent: SyntaxNode,
+    index:  usize,
+    offset: usize
+}
+
+/// A node or token of a red tree
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken)
+}
+
+impl SyntaxNode {
+    /// Creates the root of a red tree
+    ///
+    /// # Arguments
+    /// * `green` - The green root node
+    pub fn new_root(green: GreenNode) -> Self {
+        Self(Rc::new(SyntaxNodeData { green, parent: None, index: 0, offset: 0 }))
+    }
+
+    /// Get the kind of the node
+    pub fn kind(&self) -> SyntaxKind { self.0.green.kind() }
+
+    /// Get the green node this node is a view of
+    pub fn green(&self) -> &GreenNode { &self.0.green }
+
+    /// Get the range of the source covered by the node
+    pub fn text_range(&self) -> ops::Range<usize> { self.0.offset..self.0.offset + self.0.green.text_len() }
+
+    /// Get the source text of the node, including all trivia below it
+    pub fn text(&self) -> Vec<u8> {
+        let mut text = Vec::with_capacity(self.0.green.text_len());
+        self.0.green.write_text(&mut text);
+        text
+    }
+
+    /// Get the parent of the node
+    ///
+    /// # Returns
+    /// `Some` with the parent, `None` for the root
+    pub fn parent(&self) -> Option<SyntaxNode> { self.0.parent.clone() }
+
+    /// Iterates over the node and its parents, ending at the root
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> {
+        iter::successors(Some(self.clone()), SyntaxNode::parent)
+    }
+
+    /// Iterates over the child nodes and tokens of the node
+    pub fn children_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> + '_ {
+        let mut offset = self.0.offset;
+        self.0.green.children().iter().enumerate().map(move |(index, child)| {
+            let element = self.child(index, child, offset);
+            offset += child.text_len();
+            element
+        })
+    }
+
+    /// Iterates over the child nodes of the node, skipping tokens
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> + '_ {
+        self.children_with_tokens().filter_map(SyntaxElement::into_node)
+    }
+
+    /// Get the first child node of the given kind
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of node to look for
+    pub fn child_of_kind(&self, kind: SyntaxKind) -> Option<SyntaxNode> { self.children().find(|it| it.kind() == kind) }
+
+    /// Get the first child token of the given kind
+    ///
+    /// # Arguments
+    /// * `kind` - The kind of token to look for
+    pub fn token_of_kind(&self, kind: SyntaxKind) -> Option<SyntaxToken> {
+        self.children_with_tokens().filter_map(SyntaxElement::into_token).find(|it| it.kind() == kind)
+    }
+
+    /// Get the next node or token with the same parent
+    pub fn next_sibling_or_token(&self) -> Option<SyntaxElement> {
+        self.parent()?.child_after(self.0.index, self.text_range().end)
+    }
+
+    /// Get the previous node or token with the same parent
+    pub fn prev_sibling_or_token(&self) -> Option<SyntaxElement> {
+        self.parent()?.child_before(self.0.index, self.0.offset)
+    }
+
+    /// Iterates over the node and every node below it, in source order
+    pub fn descendants(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.descendants_with_tokens().filter_map(SyntaxElement::into_node)
+    }
+
+    /// Iterates over the node and every node and token below it, in source order
+    pub fn descendants_with_tokens(&self) -> impl Iterator<Item = SyntaxElement> {
+        let mut stack = vec![SyntaxElement::Node(self.clone())];
+        iter::from_fn(move || {
+            let element = stack.pop()?;
+            if let SyntaxElement::Node(node) = &element {
+                let children: Vec<_> = node.children_with_tokens().collect();
+                stack.extend(children.into_iter().rev());
+            }
+            Some(element)
+        })
+    }
+
+    /// Get the first token below the node, skipping nodes without tokens
+    pub fn first_token(&self) -> Option<SyntaxToken> {
+        self.children_with_tokens().find_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(node) => node.first_token()
+        })
+    }
+
+    /// Get the last token below the node, skipping nodes without tokens
+    pub fn last_token(&self) -> Option<SyntaxToken> {
+        let mut end = self.text_range().end;
+        self.0.green.children().iter().enumerate().rev().find_map(|(index, child)| {
+            end -= child.text_len();
+            match self.child(index, child, end) {
+                SyntaxElement::Token(token) => Some(token),
+                SyntaxElement::Node(node) => node.last_token()
+            }
+        })
+    }
+
+    /// Get the token containing an offset
+    ///
+    /// # Arguments
+    /// * `offset` - Absolute offset within the node's range, a token ending at the offset is not included
+    pub fn token_at_offset(&self, offset: usize) -> Option<SyntaxToken> {
+        let child = self.children_with_tokens().find(|it| it.text_range().contains(&offset))?;
+        match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(node) => node.token_at_offset(offset)
+        }
+    }
+
+    /// Get the smallest node or token which covers a range
+    ///
+    /// # Arguments
+    /// * `range` - Absolute range within the node's range
+    pub fn covering_element(&self, range: ops::Range<usize>) -> SyntaxElement {
+        let mut node = self.clone();
+        loop {
+            let child = node.children_with_tokens().find(|it| {
+                let covered = it.text_range();
+                covered.start <= range.start && range.end <= covered.end && !covered.is_empty()
+            });
+            match child {
+                Some(SyntaxElement::Node(child)) => node = child,
+                Some(token) => return token,
+                None => return SyntaxElement::Node(node)
+            }
+        }
+    }
+
+    /// Get the child after the one at `index`, which ends at `end`.
+    fn child_after(&self, index: usize, end: usize) -> Option<SyntaxElement> {
+        let child = self.0.green.children().get(index + 1)?;
+        Some(self.child(index + 1, child, end))
+    }
+
+    /// Get the child before the one at `index`, which starts at `start`.
+    fn child_before(&self, index: usize, start: usize) -> Option<SyntaxElement> {
+        let index = index.checked_sub(1)?;
+        let child = &self.0.green.children()[index];
+        Some(self.child(index, child, start - child.text_len()))
+    }
+
+    fn child(&self, index: usize, child: &GreenElement, offset: usize) -> SyntaxElement {
+        match child {
+            GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(SyntaxNodeData {
+                green: green.clone(),
+                parent: Some(self.clone()),
+                index,
+                offset
+            }))),
+            GreenElement::Token(green) =>
+                SyntaxElement::Token(SyntaxToken { green: green.clone(), parent: self.clone(), index, offset })
+        }
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.green.ptr_eq(&other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl std::hash::Hash for SyntaxNode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0.green.0).hash(state);
+        self.0.offset.hash(state);
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{:?}@{:?}", self.kind(), self.text_range()) }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&String::from_utf8_lossy(&self.text())) }
+}
+
+impl SyntaxToken {
+    /// Get the kind of the token
+    pub fn kind(&self) -> SyntaxKind { self.green.kind() }
+
+    /// Get the green token this token is a view of
+    pub fn green(&self) -> &GreenToken { &self.green }
+
+    /// Get the source text of the token
+    pub fn text(&self) -> &[u8] { self.green.text() }
+
+    /// Get the source text of the token as a string, replacing invalid UTF-8
+    pub fn text_lossy(&self) -> Cow<'_, str> { String::from_utf8_lossy(self.text()) }
+
+    /// Get the range of the source covered by the token
+    pub fn text_range(&self) -> ops::Range<usize> { self.offset..self.offset + self.green.text_len() }
+
+    /// Get the node containing the token
+    pub fn parent(&self) -> SyntaxNode { self.parent.clone() }
+
+    /// Iterates over the nodes containing the token, ending at the root
+    pub fn ancestors(&self) -> impl Iterator<Item = SyntaxNode> { self.parent.ancestors() }
+
+    /// Get the next node or token with the same parent
+    pub fn next_sibling_or_token(&self) -> Option<SyntaxElement> { self.parent.child_after(self.index, self.text_range().end) }
+
+    /// Get the previous node or token with the same parent
+    pub fn prev_sibling_or_token(&self) -> Option<SyntaxElement> { self.parent.child_before(self.index, self.offset) }
+
+    /// Get the next token of the tree in source order, which may belong to another node
+    pub fn next_token(&self) -> Option<SyntaxToken> {
+        let mut element = SyntaxElement::Token(self.clone());
+        loop {
+            match element.next_sibling_or_token() {
+                Some(SyntaxElement::Token(token)) => return Some(token),
+                Some(SyntaxElement::Node(node)) => match node.first_token() {
+                    Some(token) => return Some(token),
+                    None => element = SyntaxElement::Node(node)
+                },
+                None => element = SyntaxElement::Node(element.parent()?)
+            }
+        }
+    }
+}
+
+impl PartialEq for SyntaxToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.green.ptr_eq(&other.green) && self.offset == other.offset
+    }
+}
+
+impl Eq for SyntaxToken {}
+
+impl std::hash::Hash for SyntaxToken {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.green.0).hash(state);
+        self.offset.hash(state);
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?} {:?}", self.kind(), self.text_range(), self.text_lossy())
+    }
+}
+
+impl SyntaxElement {
+    /// Get the kind of the node or token
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            Self::Node(node) => node.kind(),
+            Self::Token(token) => token.kind()
+        }
+    }
+
+    /// Get the range of the source covered by the node or token
+    pub fn text_range(&self) -> ops::Range<usize> {
+        match self {
+            Self::Node(node) => node.text_range(),
+            Self::Token(token) => token.text_range()
+        }
+    }
+
+    /// Get the parent of the node or token
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        match self {
+            Self::Node(node) => node.parent(),
+            Self::Token(token) => Some(token.parent())
+        }
+    }
+
+    /// Get the next node or token with the same parent
+    pub fn next_sibling_or_token(&self) -> Option<SyntaxElement> {
+        match self {
+            Self::Node(node) => node.next_sibling_or_token(),
+            Self::Token(token) => token.next_sibling_or_token()
+        }
+    }
+
+    /// Get the previous node or token with the same parent
+    pub fn prev_sibling_or_token(&self) -> Option<SyntaxElement> {
+        match self {
+            Self::Node(node) => node.prev_sibling_or_token(),
+            Self::Token(token) => token.prev_sibling_or_token()
+        }
+    }
+
+    /// Get the node, `None` for tokens
+    pub fn into_node(self) -> Option<SyntaxNode> {
+        match self {
+            Self::Node(node) => Some(node),
+            Self::Token(_) => None
+        }
+    }
+
+    /// Get the token, `None` for nodes
+    pub fn into_token(self) -> Option<SyntaxToken> {
+        match self {
+            Self::Node(_) => None,
+            Self::Token(token) => Some(token)
+        }
+    }
+}
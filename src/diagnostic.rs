@@ -0,0 +1,290 @@
+use std::fmt::{self, Write};
+use crate::source_db::{FileId, FileSpan, SourceDb, SourceFile};
+
+/// How serious a `Diagnostic` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help
+}
+
+impl Severity {
+    /// Get the lowercase name used when rendering
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Note => "note",
+            Self::Help => "help"
+        }
+    }
+
+    fn colour(self) -> &'static str {
+        match self {
+            Self::Error => "\x1b[1;31m",
+            Self::Warning => "\x1b[1;33m",
+            Self::Note => "\x1b[1;32m",
+            Self::Help => "\x1b[1;36m"
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+/// A span of a file annotated with a message
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+    pub span:    FileSpan,
+    pub message: String,
+    /// Primary labels mark where the problem is, secondary labels add context
+    pub primary: bool
+}
+
+impl Label {
+    /// Creates a label marking where the problem is
+    ///
+    /// # Arguments
+    /// * `span` - The span to mark
+    /// * `message` - Text shown next to the span, may be empty
+    pub fn primary(span: FileSpan, message: impl Into<String>) -> Self {
+        Self { span, message: message.into(), primary: true }
+    }
+
+    /// Creates a label adding context to a diagnostic
+    ///
+    /// # Arguments
+    /// * `span` - The span to mark
+    /// * `message` - Text shown next to the span, may be empty
+    pub fn secondary(span: FileSpan, message: impl Into<String>) -> Self {
+        Self { span, message: message.into(), primary: false }
+    }
+}
+
+/// A message about a problem in one or more files, rendered for terminals with `render` or for tools with `to_json`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code:     Option<String>,
+    pub message:  String,
+    pub labels:   Vec<Label>,
+    pub notes:    Vec<String>,
+    pub help:     Vec<String>
+}
+
+/// Errors which can be reported as a `Diagnostic`
+pub trait ToDiagnostic {
+    /// Describes the error as a diagnostic
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self { severity, code: None, message: message.into(), labels: vec![], notes: vec![], help: vec![] }
+    }
+
+    /// Creates a diagnostic with `Severity::Error`
+    pub fn error(message: impl Into<String>) -> Self { Self::new(Severity::Error, message) }
+
+    /// Creates a diagnostic with `Severity::Warning`
+    pub fn warning(message: impl Into<String>) -> Self { Self::new(Severity::Warning, message) }
+
+    /// Sets the code identifying the kind of problem, such as `unknown-directive`
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Adds a label, see `Label::primary` and `Label::secondary`
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    /// Adds a note explaining the problem
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Adds a suggestion on how to fix the problem
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Renders the diagnostic for a terminal, with an excerpt of every labelled line
+    ///
+    /// Labels of files missing from `sources` are rendered as their file id and offset.
+    ///
+    /// # Arguments
+    /// * `sources` - The files the labels refer to
+    /// * `colour` - Whether to colour the output with ANSI escape codes
+    ///
+    /// # Returns
+    /// The rendered text, ending with a line break
+    pub fn render(&self, sources: &SourceDb, colour: bool) -> String {
+        let style = Style { colour };
+        let mut out = String::new();
+        let severity = self.severity;
+        let _ = match &self.code {
+            Some(code) => write!(out, "{}", style.paint(severity.colour(), &format!("{severity}[{code}]"))),
+            None => write!(out, "{}", style.paint(severity.colour(), severity.as_str()))
+        };
+        let _ = writeln!(out, "{}", style.paint("\x1b[1m", &format!(": {}", self.message)));
+
+        let mut files: Vec<FileId> = vec![];
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|it| !it.primary);
+        for label in &labels {
+            if !files.contains(&label.span.file) { files.push(label.span.file); }
+        }
+        let width = labels.iter()
+            .filter_map(|it| sources.get(it.span.file)?.line_col(it.span.range.start))
+            .map(|it| (it.line + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        for (index, file) in files.iter().enumerate() {
+            let arrow = if index == 0 { "-->" } else { ":::" };
+            let in_file: Vec<&Label> = labels.iter().copied().filter(|it| it.span.file == *file).collect();
+            let Some(source) = sources.get(*file) else {
+                let _ = writeln!(out, "{:width$}{} {file}@{}", "", style.gutter(arrow), in_file[0].span.range.start);
+                continue
+            };
+            let start = source.line_col(in_file[0].span.range.start).unwrap_or_default();
+            let _ = writeln!(out, "{:width$}{} {}:{start}", "", style.gutter(arrow), source.name());
+            render_snippet(&mut out, &style, severity, source, &in_file, width);
+        }
+
+        for (kind, messages) in [("note", &self.notes), ("help", &self.help)] {
+            for message in messages {
+                let _ = writeln!(out, "{:width$} {} {}: {message}", "", style.gutter("="), style.paint("\x1b[1m", kind));
+            }
+        }
+        out
+    }
+
+    /// Renders the diagnostic as a single line of JSON for editors and CI
+    ///
+    /// Lines and columns are one-based, offsets are zero-based byte offsets. Files missing from
+    /// `sources` have a `null` name, line and column.
+    ///
+    /// # Arguments
+    /// * `sources` - The files the labels refer to
+    pub fn to_json(&self, sources: &SourceDb) -> String {
+        let mut out = String::from("{");
+        let _ = write!(out, "\"severity\":{},", json_string(self.severity.as_str()));
+        let _ = write!(out, "\"code\":{},", self.code.as_deref().map_or_else(|| "null".to_string(), json_string));
+        let _ = write!(out, "\"message\":{},\"labels\":[", json_string(&self.message));
+        for (index, label) in self.labels.iter().enumerate() {
+            if index > 0 { out.push(','); }
+            let source = sources.get(label.span.file);
+            let location = |offset| {
+                let location = source.and_then(|it| it.line_col(offset));
+                let line = location.map_or_else(|| "null".to_string(), |it| (it.line + 1).to_string());
+                let column = location.map_or_else(|| "null".to_string(), |it| (it.column + 1).to_string());
+                (line, column)
+            };
+            let (line, column) = location(label.span.range.start);
+            let (end_line, end_column) = location(label.span.range.end);
+            let _ = write!(
+                out,
+                "{{\"file\":{},\"file_id\":{},\"start\":{},\"end\":{},\"line\":{line},\"column\":{column},\
+                 \"end_line\":{end_line},\"end_column\":{end_column},\"primary\":{},\"message\":{}}}",
+                source.map_or_else(|| "null".to_string(), |it| json_string(it.name())),
+                label.span.file.index(),
+                label.span.range.start,
+                label.span.range.end,
+                label.primary,
+                json_string(&label.message)
+            );
+        }
+        let _ = write!(out, "],\"notes\":{},\"help\":{}}}", json_array(&self.notes), json_array(&self.help));
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{}[{code}]: {}", self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message)
+        }
+    }
+}
+
+struct Style {
+    colour: bool
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        match self.colour {
+            true => format!("{code}{text}\x1b[0m"),
+            false => text.to_string()
+        }
+    }
+
+    fn gutter(&self, text: &str) -> String { self.paint("\x1b[1;34m", text) }
+}
+
+/// Writes the labelled lines of a file, each followed by the carets of its labels.
+fn render_snippet(out: &mut String, style: &Style, severity: Severity, source: &SourceFile<u8>, labels: &[&Label], width: usize) {
+    let lines = source.line_index();
+    let mut marks: Vec<(usize, &Label)> = labels.iter()
+        .filter_map(|it| Some((source.line_col(it.span.range.start)?.line, *it)))
+        .collect();
+    marks.sort_by_key(|(line, label)| (*line, label.span.range.start));
+    let _ = writeln!(out, "{:width$} {}", "", style.gutter("|"));
+    let mut previous: Option<usize> = None;
+    for (line, label) in &marks {
+        let range = lines.line_range(*line).unwrap_or_default();
+        if previous != Some(*line) {
+            if previous.is_some_and(|it| it + 1 < *line) {
+                let _ = writeln!(out, "{}", style.gutter("..."));
+            }
+            let text = String::from_utf8_lossy(&source.contents()[range.clone()]);
+            let _ = writeln!(out, "{} {} {text}", style.gutter(&format!("{:>width$}", line + 1)), style.gutter("|"));
+            previous = Some(*line);
+        }
+        let start = label.span.range.start.clamp(range.start, range.end);
+        let end = label.span.range.end.clamp(start, range.end);
+        let indent: String = String::from_utf8_lossy(&source.contents()[range.start..start]).chars()
+            .map(|it| if it == '\t' { '\t' } else { ' ' })
+            .collect();
+        let length = String::from_utf8_lossy(&source.contents()[start..end]).chars().count().max(1);
+        let (mark, code) = match label.primary {
+            true => ("^", severity.colour()),
+            false => ("-", "\x1b[1;34m")
+        };
+        let underline = format!("{}{}{}", mark.repeat(length), if label.message.is_empty() { "" } else { " " }, label.message);
+        let _ = writeln!(out, "{:width$} {} {indent}{}", "", style.gutter("|"), style.paint(code, &underline));
+    }
+    let _ = writeln!(out, "{:width$} {}", "", style.gutter("|"));
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            it if (it as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", it as u32); }
+            it => out.push(it)
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_array(items: &[String]) -> String {
+    format!("[{}]", items.iter().map(|it| json_string(it)).collect::<Vec<_>>().join(","))
+}
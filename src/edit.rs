@@ -0,0 +1,97 @@
+use std::ops;
+
+/// A growable sequence with a movable gap, making repeated edits near each other cheap
+///
+/// Elements before the gap are stored in order, elements after it are stored in reverse so the gap
+/// can be moved by shifting elements from one side to the other.
+///
+/// # Type Parameters
+/// * `T` - The type of the elements being stored.
+#[derive(Debug, Clone)]
+pub struct GapBuffer<T> {
+    front: Vec<T>,
+    back:  Vec<T>
+}
+
+impl<T> GapBuffer<T> {
+    pub fn new() -> Self { Self { front: vec![], back: vec![] } }
+
+    /// Get the number of elements in the buffer
+    ///
+    /// # Returns
+    /// Length of the buffer as usize
+    pub fn len(&self) -> usize { self.front.len() + self.back.len() }
+
+    /// Check if the buffer is empty
+    ///
+    /// # Returns
+    /// Boolean that's true if the buffer has no elements
+    pub fn is_empty(&self) -> bool { self.front.is_empty() && self.back.is_empty() }
+
+    /// Get the position of the gap
+    ///
+    /// # Returns
+    /// Index of the first element after the gap
+    pub fn gap(&self) -> usize { self.front.len() }
+
+    /// Get the element at an index
+    ///
+    /// # Arguments
+    /// * `index` - Index of the element, ignoring the gap
+    ///
+    /// # Returns
+    /// `Some` with a reference to the element, `None` if the index is out of bounds
+    pub fn get(&self, index: usize) -> Option<&T> {
+        match index.checked_sub(self.front.len()) {
+            None => self.front.get(index),
+            Some(offset) => self.back.len().checked_sub(offset + 1).map(|it| &self.back[it])
+        }
+    }
+
+    /// Moves the gap so it starts at the given index
+    ///
+    /// # Arguments
+    /// * `position` - The index to move the gap to, clamped to the length of the buffer
+    pub fn move_gap(&mut self, position: usize) {
+        let position = position.min(self.len());
+        if position < self.front.len() {
+            self.back.extend(self.front.drain(position..).rev());
+        } else {
+            let split = self.back.len() - (position - self.front.len());
+            self.front.extend(self.back.drain(split..).rev());
+        }
+    }
+
+    /// Replaces a range of elements, leaving the gap after the replacement
+    ///
+    /// # Arguments
+    /// * `range` - The range to remove, which must be within the buffer
+    /// * `replacement` - The elements to insert in its place
+    ///
+    /// # Returns
+    /// The removed elements
+    pub fn splice<I: IntoIterator<Item = T>>(&mut self, range: ops::Range<usize>, replacement: I) -> Vec<T> {
+        self.move_gap(range.end);
+        let removed = self.front.drain(range.start..).collect();
+        self.front.extend(replacement);
+        removed
+    }
+
+    /// Consumes the buffer, closing the gap
+    ///
+    /// # Returns
+    /// The elements as a contiguous vector
+    pub fn into_vec(self) -> Vec<T> {
+        let mut result = self.front;
+        result.extend(self.back.into_iter().rev());
+        result
+    }
+}
+
+impl<T> Default for GapBuffer<T> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<T> From<Vec<T>> for GapBuffer<T> {
+    fn from(front: Vec<T>) -> Self { Self { front, back: vec![] } }
+}
@@ -0,0 +1,106 @@
+use std::{error, fmt, io, ops};
+
+/// Result type used by the read, lexer and parse layers
+pub type Result<T, E = BexError> = std::result::Result<T, E>;
+
+/// Errors raised while reading, lexing or parsing a sequence
+///
+/// Every variant except `Io` carries the position it was raised at, see `BexError::span`.
+#[derive(Debug)]
+pub enum BexError {
+    /// The end of the sequence was reached while more elements were required
+    UnexpectedEof { pos: usize },
+    /// An element or token did not match what was expected
+    Unexpected { found: String, expected: String, span: ops::Range<usize> },
+    /// The bytes in the range are not valid UTF-8 or end in the middle of a character
+    InvalidUtf8 { span: ops::Range<usize> },
+    /// The cursor was moved past the end of the sequence
+    CursorOutOfBounds { pos: usize, len: usize },
+    /// The cursor was moved back past the start of the sequence
+    CursorUnderflow { pos: usize, count: usize },
+    /// A range does not lie within the sequence
+    RangeOutOfBounds { range: ops::Range<usize>, len: usize },
+    /// The cursor was moved before the part of a stream which is still retained in memory
+    RewindPastWindow { pos: usize, window_start: usize },
+    /// The underlying reader failed
+    Io(io::Error)
+}
+
+impl BexError {
+    /// Creates an `Unexpected` error from the element that was found
+    ///
+    /// # Arguments
+    /// * `found` - The element or token that was found
+    /// * `expected` - Description of what was expected instead
+    /// * `span` - The range of the sequence `found` was read from
+    pub fn unexpected<T: fmt::Debug>(found: &T, expected: impl Into<String>, span: ops::Range<usize>) -> Self {
+        Self::Unexpected { found: format!("{found:?}"), expected: expected.into(), span }
+    }
+
+    /// Get the range of the sequence the error was raised at
+    ///
+    /// # Returns
+    /// `Some` with the range, `None` for errors without a position
+    pub fn span(&self) -> Option<ops::Range<usize>> {
+        match self {
+            Self::UnexpectedEof { pos } => Some(*pos..*pos),
+            Self::Unexpected { span, .. } => Some(span.clone()),
+            Self::InvalidUtf8 { span } => Some(span.clone()),
+            Self::CursorOutOfBounds { pos, .. } => Some(*pos..*pos),
+            Self::CursorUnderflow { pos, .. } => Some(*pos..*pos),
+            Self::RangeOutOfBounds { range, .. } => Some(range.clone()),
+            Self::RewindPastWindow { pos, .. } => Some(*pos..*pos),
+            Self::Io(_) => None
+        }
+    }
+}
+
+impl fmt::Display for BexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof { pos } =>
+                write!(f, "End of file was reached unexpectedly at offset {pos}."),
+            Self::Unexpected { found, expected, span } =>
+                write!(f, "Expected {expected} but found {found} at offset {}.", span.start),
+            Self::InvalidUtf8 { span } =>
+                write!(f, "Invalid UTF-8 sequence of {} bytes at offset {}.", span.len(), span.start),
+            Self::CursorOutOfBounds { pos, len } =>
+                write!(f, "Cursor position {pos} is out of bounds for a sequence of length {len}."),
+            Self::CursorUnderflow { pos, count } =>
+                write!(f, "Cursor cannot move back {count} positions from offset {pos}."),
+            Self::RangeOutOfBounds { range, len } =>
+                write!(f, "Range {range:?} is out of bounds for a sequence of length {len}."),
+            Self::RewindPastWindow { pos, window_start } =>
+                write!(f, "Cannot rewind to offset {pos}, only offsets from {window_start} are retained."),
+            Self::Io(e) => e.fmt(f)
+        }
+    }
+}
+
+impl error::Error for BexError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for BexError {
+    fn from(e: io::Error) -> Self { Self::Io(e) }
+}
+
+impl From<BexError> for io::Error {
+    fn from(e: BexError) -> Self {
+        let kind = match e {
+            BexError::Io(e) => return e,
+            BexError::UnexpectedEof { .. } => io::ErrorKind::UnexpectedEof,
+            BexError::Unexpected { .. } | BexError::InvalidUtf8 { .. } => io::ErrorKind::InvalidData,
+            BexError::CursorOutOfBounds { .. }
+                | BexError::CursorUnderflow { .. }
+                | BexError::RangeOutOfBounds { .. }
+                | BexError::RewindPastWindow { .. } => io::ErrorKind::InvalidInput
+        };
+        io::Error::new(kind, e)
+    }
+}
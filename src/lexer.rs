@@ -1,112 +1,577 @@
-use std::error::Error;
+use std::borrow::Cow;
+use std::cell::OnceCell;
 use std::fmt::Debug;
-use std::{io, ops};
-use crate::read::Analyser;
+use std::ops;
+use std::sync::Arc;
+use crate::edit::GapBuffer;
+use crate::error::{BexError, Result};
+use crate::location::{LineCol, LineIndex, LineSource, Spanned};
+use crate::mode::{ErasedModes, ModeStack};
+use crate::read::{peek_contents, Analyser, Checkpoint, SliceAnalyser};
+use crate::source_db::{FileId, FileSpan};
 
 /// Lexer struct which contains current cursor position and contents to analyze
 ///
+/// The contents are held as a `Cow`, so a lexer can either borrow the sequence it analyzes
+/// (see `Lexer::from_slice`) or own it (see `Lexer::new` and `Lexer::from_vec`).
+///
 /// # Type Parameters
+/// * `'a` - Lifetime of the borrowed contents, tokens may hold slices of the source for this lifetime.
 /// * `T` - Any type that is Sized (has a constant size in memory), and can be compared for equality.
-pub struct Lexer<T: Sized + PartialEq + Copy> {
+pub struct Lexer<'a, T: Sized + PartialEq + Clone> {
     cursor:      usize,
-    contents:    Vec<T>
+    contents:    Cow<'a, [T]>,
+    file:        Option<FileId>,
+    modes:       Option<Box<dyn ErasedModes>>,
+    lines:       OnceCell<Arc<LineIndex>>
 }
 
-impl<T: Sized + PartialEq + Copy> Lexer<T> {
+impl<'a, T: Sized + PartialEq + Clone> Lexer<'a, T> {
+    /// Creates a lexer which owns a copy of the given content
+    ///
+    /// # Arguments
+    /// * `content` - The sequence to copy into the lexer
     pub fn new<C: AsRef<[T]>>(content: C) -> Self {
-        Self {
-            cursor: 0,
-            contents: content.as_ref().to_vec(),
+        Self::from_vec(content.as_ref().to_vec())
+    }
+
+    /// Creates a lexer which borrows the given content without copying it
+    ///
+    /// # Arguments
+    /// * `content` - The sequence to analyze
+    pub fn from_slice(content: &'a [T]) -> Self {
+        Self::from(Cow::Borrowed(content))
+    }
+
+    /// Creates a lexer which takes ownership of the given content without copying it
+    ///
+    /// # Arguments
+    /// * `content` - The sequence to analyze
+    pub fn from_vec(content: Vec<T>) -> Self {
+        Self::from(Cow::Owned(content))
+    }
+
+    /// Tags the lexer with the file its contents were loaded from
+    ///
+    /// # Arguments
+    /// * `file` - Id of the file in its `SourceDb`
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    /// Get the file the contents were loaded from
+    ///
+    /// # Returns
+    /// `Some` with the id if the lexer was created by a `SourceDb` or tagged with `with_file`
+    pub fn file(&self) -> Option<FileId> { self.file }
+
+    /// Get a span of the file the contents were loaded from
+    ///
+    /// # Arguments
+    /// * `range` - The range of the sequence
+    ///
+    /// # Returns
+    /// `Some` with the span if the lexer is tagged with a file, otherwise `None`
+    pub fn file_span(&self, range: ops::Range<usize>) -> Option<FileSpan> {
+        self.file.map(|file| FileSpan::new(file, range))
+    }
+
+    /// Get the mode stack kept between tokens
+    ///
+    /// # Type Parameters
+    /// * `S` - The type of the modes, usually `ScopedToken::Scope`
+    ///
+    /// # Returns
+    /// `Some` with the stack, `None` if no stack of modes of type `S` has been created
+    pub fn modes<S: 'static>(&self) -> Option<&ModeStack<S>> {
+        self.modes.as_ref()?.as_any().downcast_ref()
+    }
+
+    /// Get the mode stack kept between tokens mutably, creating it if needed
+    ///
+    /// A lexer keeps a single stack, so a stack of modes of another type is replaced. The stack
+    /// is saved by `Analyser::checkpoint` and restored by `Analyser::rollback` with the cursor.
+    ///
+    /// # Type Parameters
+    /// * `S` - The type of the modes, usually `ScopedToken::Scope`
+    pub fn modes_mut<S: Default + Clone + Send + 'static>(&mut self) -> &mut ModeStack<S> {
+        if !self.modes.as_ref().is_some_and(|it| it.as_any().is::<ModeStack<S>>()) {
+            self.modes = Some(Box::new(ModeStack::<S>::default()));
+        }
+        self.modes.as_mut().and_then(|it| it.as_any_mut().downcast_mut()).expect("mode stack was just created")
+    }
+
+    /// Removes the mode stack from the lexer, so it can be borrowed alongside the lexer
+    ///
+    /// # Returns
+    /// The stack, or a new stack in the base mode if there is no stack of modes of type `S`
+    pub fn take_modes<S: Default + 'static>(&mut self) -> ModeStack<S> {
+        match self.modes.take().map(|it| it.into_any().downcast::<ModeStack<S>>()) {
+            Some(Ok(modes)) => *modes,
+            _ => ModeStack::default()
+        }
+    }
+
+    /// Gives the lexer a mode stack, replacing its current one
+    ///
+    /// # Arguments
+    /// * `modes` - The stack to keep between tokens
+    pub fn set_modes<S: Clone + Send + 'static>(&mut self, modes: ModeStack<S>) { self.modes = Some(Box::new(modes)); }
+
+    /// Check if the lexer is still borrowing its contents
+    ///
+    /// # Returns
+    /// Boolean that's true if the contents have not been copied or modified
+    pub fn is_borrowed(&self) -> bool { matches!(self.contents, Cow::Borrowed(_)) }
+
+    /// Get a slice of the borrowed source that outlives the lexer
+    ///
+    /// # Arguments
+    /// * `range` - The range of the sequence to slice
+    ///
+    /// # Returns
+    /// `Some` with the slice if the contents are borrowed and the range is in bounds, otherwise `None`
+    pub fn source_slice(&self, range: ops::Range<usize>) -> Option<&'a [T]> {
+        match self.contents {
+            Cow::Borrowed(contents) => contents.get(range),
+            Cow::Owned(_) => None
+        }
+    }
+
+    /// Get a slice of the source, borrowed when possible and copied otherwise
+    ///
+    /// # Arguments
+    /// * `range` - The range of the sequence to slice
+    ///
+    /// # Returns
+    /// `Some` with the slice if the range is in bounds, otherwise `None`
+    pub fn source_cow(&self, range: ops::Range<usize>) -> Option<Cow<'a, [T]>> {
+        match self.contents {
+            Cow::Borrowed(contents) => contents.get(range).map(Cow::Borrowed),
+            Cow::Owned(ref contents) => contents.get(range).map(|it| Cow::Owned(it.to_vec()))
         }
     }
 
-    pub fn extract(
-        &mut self,
-        range: ops::Range<usize>
-    ) -> Vec<T> {
-        let start = range.start;
-        let end = range.end;
-        let extraction_result = self.contents.drain(range).collect::<Vec<T>>();
+    /// Removes a range of elements from the contents
+    ///
+    /// # Arguments
+    /// * `range` - The range to remove
+    ///
+    /// # Returns
+    /// `Result<Vec<T>>` - Ok with the removed elements, otherwise an Err with the `BexError` if the range is out of bounds
+    pub fn extract(&mut self, range: ops::Range<usize>) -> Result<Vec<T>> {
+        self.splice(range, [])
+    }
+
+    /// Inserts elements before the given index
+    ///
+    /// # Arguments
+    /// * `index` - The index to insert at, the cursor stays in place if it is at this index
+    /// * `elements` - The elements to insert
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError` if the index is out of bounds
+    pub fn insert_at(&mut self, index: usize, elements: &[T]) -> Result<()> {
+        self.splice(index..index, elements.iter().cloned()).map(|_| ())
+    }
+
+    /// Replaces a range of elements with a copy of the given elements
+    ///
+    /// # Arguments
+    /// * `range` - The range to replace
+    /// * `elements` - The elements to insert in its place
+    ///
+    /// # Returns
+    /// `Result<Vec<T>>` - Ok with the replaced elements, otherwise an Err with the `BexError` if the range is out of bounds
+    pub fn replace_range(&mut self, range: ops::Range<usize>, elements: &[T]) -> Result<Vec<T>> {
+        self.splice(range, elements.iter().cloned())
+    }
+
+    /// Replaces a range of elements, see `LexerEdit` for how the cursor is moved
+    ///
+    /// Every call moves the elements after the range like `Vec::splice`. Use `edit()` to apply many
+    /// edits, it keeps the contents in a `GapBuffer` between them.
+    ///
+    /// # Arguments
+    /// * `range` - The range to replace
+    /// * `replacement` - The elements to insert in its place
+    ///
+    /// # Returns
+    /// `Result<Vec<T>>` - Ok with the replaced elements, otherwise an Err with the `BexError` if the range is out of bounds
+    pub fn splice<I: IntoIterator<Item = T>>(&mut self, range: ops::Range<usize>, replacement: I) -> Result<Vec<T>> {
+        if range.start > range.end || range.end > self.len() {
+            return Err(BexError::RangeOutOfBounds { range, len: self.len() })
+        }
+        self.lines.take();
+        let contents = self.contents.to_mut();
+        let old_len = contents.len();
+        let removed: Vec<T> = contents.splice(range.clone(), replacement).collect();
+        let inserted = contents.len() + removed.len() - old_len;
+        self.cursor = cursor_after_splice(self.cursor, &range, removed.len(), inserted);
+        Ok(removed)
+    }
+
+    /// Starts a batch of edits on the contents
+    ///
+    /// The contents are moved into a `GapBuffer` for the lifetime of the returned guard, so many
+    /// edits close to each other only move the elements between them. Borrowed contents are copied.
+    /// The cached line index is discarded.
+    ///
+    /// # Returns
+    /// A `LexerEdit` which writes the edited contents back when dropped
+    pub fn edit(&mut self) -> LexerEdit<'_, 'a, T> {
+        self.lines.take();
+        let buffer = GapBuffer::from(std::mem::take(self.contents.to_mut()));
+        LexerEdit { lexer: self, buffer }
+    }
+}
+
+/// A batch of edits on the contents of a `Lexer`, created by `Lexer::edit`
+///
+/// The cursor of the lexer is moved with the edits:
+/// * edits entirely after the cursor leave it in place.
+/// * inserting at the cursor leaves its position in place, so it is on the first inserted element.
+/// * edits entirely before the cursor shift it by the change in length, so it stays on the same element.
+/// * edits replacing a range containing the cursor move it to the start of the replacement.
+pub struct LexerEdit<'l, 'a, T: Sized + PartialEq + Clone> {
+    lexer:  &'l mut Lexer<'a, T>,
+    buffer: GapBuffer<T>
+}
+
+impl<T: Sized + PartialEq + Clone> LexerEdit<'_, '_, T> {
+    /// Get the length of the contents being edited
+    ///
+    /// # Returns
+    /// Length of the contents as usize
+    pub fn len(&self) -> usize { self.buffer.len() }
+
+    /// Check if the contents being edited are empty
+    ///
+    /// # Returns
+    /// Boolean that's true if there are no elements
+    pub fn is_empty(&self) -> bool { self.buffer.is_empty() }
+
+    /// Get the cursor position of the lexer after the edits made so far
+    ///
+    /// # Returns
+    /// Cursor position as usize
+    pub fn cursor(&self) -> usize { self.lexer.cursor }
+
+    /// Get the element at an index
+    ///
+    /// # Arguments
+    /// * `index` - Index of the element
+    ///
+    /// # Returns
+    /// `Some` with a reference to the element, `None` if the index is out of bounds
+    pub fn get(&self, index: usize) -> Option<&T> { self.buffer.get(index) }
+
+    /// Inserts elements before the given index
+    ///
+    /// # Arguments
+    /// * `index` - The index to insert at
+    /// * `elements` - The elements to insert
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError` if the index is out of bounds
+    pub fn insert_at(&mut self, index: usize, elements: &[T]) -> Result<()> {
+        self.splice(index..index, elements.iter().cloned()).map(|_| ())
+    }
+
+    /// Replaces a range of elements with a copy of the given elements
+    ///
+    /// # Arguments
+    /// * `range` - The range to replace
+    /// * `elements` - The elements to insert in its place
+    ///
+    /// # Returns
+    /// `Result<Vec<T>>` - Ok with the replaced elements, otherwise an Err with the `BexError` if the range is out of bounds
+    pub fn replace_range(&mut self, range: ops::Range<usize>, elements: &[T]) -> Result<Vec<T>> {
+        self.splice(range, elements.iter().cloned())
+    }
 
-        if start <= self.cursor && self.cursor < end {
-            self.cursor = start;
-        } else if end < self.cursor {
-            self.cursor -= end;
+    /// Replaces a range of elements and moves the cursor of the lexer accordingly
+    ///
+    /// # Arguments
+    /// * `range` - The range to replace
+    /// * `replacement` - The elements to insert in its place
+    ///
+    /// # Returns
+    /// `Result<Vec<T>>` - Ok with the replaced elements, otherwise an Err with the `BexError` if the range is out of bounds
+    pub fn splice<I: IntoIterator<Item = T>>(&mut self, range: ops::Range<usize>, replacement: I) -> Result<Vec<T>> {
+        if range.start > range.end || range.end > self.buffer.len() {
+            return Err(BexError::RangeOutOfBounds { range, len: self.buffer.len() })
         }
+        let old_len = self.buffer.len();
+        let removed = self.buffer.splice(range.clone(), replacement);
+        let inserted = self.buffer.len() + removed.len() - old_len;
+        self.lexer.cursor = cursor_after_splice(self.lexer.cursor, &range, removed.len(), inserted);
+        Ok(removed)
+    }
+}
+
+/// Moves a cursor over a splice of `range`, as documented on `LexerEdit`.
+fn cursor_after_splice(cursor: usize, range: &ops::Range<usize>, removed: usize, inserted: usize) -> usize {
+    if cursor >= range.end && cursor > range.start {
+        cursor - removed + inserted
+    } else if cursor > range.start {
+        range.start
+    } else {
+        cursor
+    }
+}
+
+impl<T: Sized + PartialEq + Clone> Drop for LexerEdit<'_, '_, T> {
+    fn drop(&mut self) {
+        self.lexer.contents = Cow::Owned(std::mem::take(&mut self.buffer).into_vec());
+    }
+}
+
+impl<T: LineSource> Lexer<'_, T> {
+    /// Get the line index of the contents, built on first use and rebuilt after the contents are edited
+    pub fn line_index(&self) -> &Arc<LineIndex> {
+        self.lines.get_or_init(|| Arc::new(LineIndex::new(&self.contents)))
+    }
 
-        extraction_result
+    /// Converts an offset into a human-readable line and column
+    ///
+    /// # Arguments
+    /// * `offset` - Offset within the contents
+    ///
+    /// # Returns
+    /// `Some` with the location of the offset, `None` if the offset is out of bounds
+    pub fn line_col(&self, offset: usize) -> Option<LineCol> { self.line_index().line_col(offset) }
+
+    /// Get the line and column of the cursor
+    ///
+    /// # Returns
+    /// `Some` with the location of the cursor, `None` if the cursor is out of bounds
+    pub fn location(&self) -> Option<LineCol> { self.line_col(self.cursor) }
+}
+
+impl<'a, T: Sized + PartialEq + Clone> From<Cow<'a, [T]>> for Lexer<'a, T> {
+    fn from(contents: Cow<'a, [T]>) -> Self {
+        Self {
+            cursor: 0,
+            contents,
+            file: None,
+            modes: None,
+            lines: OnceCell::new()
+        }
     }
 }
 
 /// Defines methods for generating a token.
-pub trait Token<T: Sized + PartialEq + Copy> where Self: Sized {
-    type Error: From<io::Error> + Debug;
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the lexer's source, allowing tokens to hold slices of it.
+/// * `T` - The type of the elements being lexed.
+pub trait Token<'a, T: Sized + PartialEq + Clone> where Self: Sized {
+    type Error: From<BexError> + Debug;
 
     /// Generates the next token from Lexer.
     ///
     /// # Arguments
     ///
     /// * `lexer` - Lexer from which the token should be generated.
-    fn next_token(lexer: &mut Lexer<T>) -> Result<Self, Self::Error>;
+    fn next_token(lexer: &mut Lexer<'a, T>) -> Result<Self, Self::Error>;
+
+    /// Skips input which is ignored between tokens, such as whitespace. Called by the tokenizing
+    /// methods before checking for the end, so input ending in ignored elements has no extra token.
+    /// Skips nothing by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `lexer` - Lexer whose cursor is moved past the ignored input.
+    fn skip_ignored(lexer: &mut Lexer<'a, T>) -> Result<(), Self::Error> {
+        let _ = lexer;
+        Ok(())
+    }
 }
 
-/// Defines methods for generating a token using a specific lexical scope (can be used for lexer-hacks).
-pub trait ScopedToken<T: Sized + PartialEq + Copy> where Self: Sized {
-    type Scope: Default;
-    type Error: From<io::Error> + Debug;
+/// Defines methods for generating a token using a stack of lexical scopes (can be used for lexer-hacks).
+///
+/// The stack is owned by the lexer and kept between tokens, so a token can push a scope such as
+/// "inside string" and the tokens after it are generated in that scope until it is popped.
+pub trait ScopedToken<'a, T: Sized + PartialEq + Clone> where Self: Sized {
+    type Scope: Default + Clone + Send + 'static;
+    type Error: From<BexError> + Debug;
 
-    /// Generates the next token from Lexer using the defined Scope.
+    /// Generates the next token from Lexer using the current scope.
     ///
     /// # Arguments
     ///
     /// * `lexer` - Lexer from which the token should be generated.
-    /// * `scope` - the scope for generating the token.
-    fn next_token(lexer: &mut Lexer<T>, scope: &mut Self::Scope) -> Result<Self, Self::Error>;
+    /// * `scopes` - the scopes for generating the token, the base scope is `Scope::default()`.
+    fn next_token(lexer: &mut Lexer<'a, T>, scopes: &mut ModeStack<Self::Scope>) -> Result<Self, Self::Error>;
+
+    /// Skips input which is ignored between tokens in the current scope, see `Token::skip_ignored`.
+    /// Skips nothing by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `lexer` - Lexer whose cursor is moved past the ignored input.
+    /// * `scopes` - the scopes the input is skipped in.
+    fn skip_ignored(lexer: &mut Lexer<'a, T>, scopes: &mut ModeStack<Self::Scope>) -> Result<(), Self::Error> {
+        let _ = (lexer, scopes);
+        Ok(())
+    }
 }
 
-impl<T: Sized + PartialEq + Copy, Scoped: ScopedToken<T>> Token<T> for Scoped {
-    type Error = <Scoped as ScopedToken<T>>::Error;
+impl<'a, T: Sized + PartialEq + Clone, Scoped: ScopedToken<'a, T>> Token<'a, T> for Scoped {
+    type Error = <Scoped as ScopedToken<'a, T>>::Error;
 
-    /// Generates the next token in the default scope.
+    /// Generates the next token using the scopes kept by the lexer.
     ///
     /// # Arguments
     ///
     /// * `lexer` - Lexer from which the token should be generated.
-    fn next_token(lexer: &mut Lexer<T>) -> Result<Self, Self::Error> {
-        <Scoped as ScopedToken<T>>::next_token(lexer, &mut Scoped::Scope::default())
+    fn next_token(lexer: &mut Lexer<'a, T>) -> Result<Self, Self::Error> {
+        let mut scopes = lexer.take_modes::<Scoped::Scope>();
+        let result = <Scoped as ScopedToken<'a, T>>::next_token(lexer, &mut scopes);
+        lexer.set_modes(scopes);
+        result
+    }
+
+    /// Skips ignored input using the scopes kept by the lexer.
+    ///
+    /// # Arguments
+    ///
+    /// * `lexer` - Lexer whose cursor is moved past the ignored input.
+    fn skip_ignored(lexer: &mut Lexer<'a, T>) -> Result<(), Self::Error> {
+        let mut scopes = lexer.take_modes::<Scoped::Scope>();
+        let result = <Scoped as ScopedToken<'a, T>>::skip_ignored(lexer, &mut scopes);
+        lexer.set_modes(scopes);
+        result
     }
 }
 
-impl<T: Sized + PartialEq + Copy> Lexer<T> {
+impl<'a, T: Sized + PartialEq + Clone> Lexer<'a, T> {
     pub fn tokenize_until_end<
-        TokenType: Token<T>
+        TokenType: Token<'a, T>
     >(mut self) -> Result<Vec<TokenType>, TokenType::Error> {
         let mut tokens = vec![];
-        while !self.is_end() {
+        loop {
+            TokenType::skip_ignored(&mut self)?;
+            if self.is_end() { break }
             tokens.push(TokenType::next_token(&mut self)?)
         }
         Ok(tokens)
     }
-}
 
-impl<T: Sized + PartialEq + Copy> Analyser<T> for Lexer<T> {
-    /// Get the entire sequence being analyzed
+    /// Tokenizes the remaining contents, recording the range each token was read from
+    ///
+    /// Spans start after the input skipped by `Token::skip_ignored`, so they exclude trivia.
     ///
     /// # Returns
-    /// Array slice of the sequence being analyzed
-    fn contents(&self) -> &[T] { &self.contents[..] }
+    /// The tokens wrapped in `Spanned`, or the first error returned by `Token::next_token`
+    pub fn tokenize_spanned<
+        TokenType: Token<'a, T>
+    >(mut self) -> Result<Vec<Spanned<TokenType>>, TokenType::Error> {
+        let mut tokens = vec![];
+        loop {
+            TokenType::skip_ignored(&mut self)?;
+            if self.is_end() { break }
+            let start = self.pos();
+            let token = TokenType::next_token(&mut self)?;
+            tokens.push(Spanned::new(token, start..self.pos()))
+        }
+        Ok(tokens)
+    }
 
-    /// Get the current position of cursor within the sequence
+    /// Tokenizes the remaining contents, collecting every error instead of stopping at the first
+    ///
+    /// After `Token::next_token` fails, the error is recorded and `recovery` moves the cursor to
+    /// where lexing resumes. If the strategy does not move the cursor past the start of the failed
+    /// token, the cursor is moved one element past it so lexing always makes progress.
+    ///
+    /// # Arguments
+    /// * `recovery` - Decides where lexing resumes after an error, see `Recovery`
     ///
     /// # Returns
-    /// Cursor position as usize
-    fn pos(&self) -> usize { self.cursor }
+    /// The tokens that were read, and every error with the range that was skipped because of it,
+    /// starting after the input skipped by `Token::skip_ignored`
+    pub fn tokenize_recovering<
+        TokenType: Token<'a, T>,
+        R: Recovery<'a, T>
+    >(mut self, mut recovery: R) -> (Vec<TokenType>, Vec<Spanned<TokenType::Error>>) {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        while !self.is_end() {
+            let mut start = self.pos();
+            let result = TokenType::skip_ignored(&mut self).and_then(|_| {
+                start = self.pos();
+                match self.is_end() {
+                    true => Ok(None),
+                    false => TokenType::next_token(&mut self).map(Some)
+                }
+            });
+            match result {
+                Ok(None) => break,
+                Ok(Some(token)) => tokens.push(token),
+                Err(error) => {
+                    recovery.recover(&mut self, start);
+                    if self.cursor <= start {
+                        self.cursor = (start + 1).min(self.len());
+                    }
+                    errors.push(Spanned::new(error, start..self.cursor));
+                }
+            }
+        }
+        (tokens, errors)
+    }
+}
 
-    /// Consumes the analyser, returning the sequence being analyzed
+/// Decides where lexing resumes after `Token::next_token` fails, see `Lexer::tokenize_recovering`
+///
+/// Closures taking the lexer and the start of the failed token implement this trait.
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the lexer's source.
+/// * `T` - The type of the elements being lexed.
+pub trait Recovery<'a, T: Sized + PartialEq + Clone> {
+    /// Moves the cursor to where lexing should resume
+    ///
+    /// # Arguments
+    /// * `lexer` - The lexer, with the cursor wherever the failed token left it
+    /// * `start` - The position the failed token started at
+    fn recover(&mut self, lexer: &mut Lexer<'a, T>, start: usize);
+}
+
+impl<'a, T: Sized + PartialEq + Clone, F: FnMut(&mut Lexer<'a, T>, usize)> Recovery<'a, T> for F {
+    fn recover(&mut self, lexer: &mut Lexer<'a, T>, start: usize) { self(lexer, start) }
+}
+
+/// Resumes lexing one element after the start of the failed token
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkipElement;
+
+impl<'a, T: Sized + PartialEq + Clone> Recovery<'a, T> for SkipElement {
+    fn recover(&mut self, lexer: &mut Lexer<'a, T>, start: usize) {
+        lexer.cursor = (start + 1).min(lexer.len());
+    }
+}
+
+/// Resumes lexing at the next element matching a predicate, such as a line break or delimiter
+///
+/// The search starts after the start of the failed token, the matching element is not skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct SkipUntil<F>(pub F);
+
+impl<'a, T: Sized + PartialEq + Clone, F: FnMut(&T) -> bool> Recovery<'a, T> for SkipUntil<F> {
+    fn recover(&mut self, lexer: &mut Lexer<'a, T>, start: usize) {
+        let from = (start + 1).min(lexer.len());
+        lexer.cursor = lexer.contents[from..].iter().position(&mut self.0).map_or(lexer.len(), |it| from + it);
+    }
+}
+
+impl<T: Sized + PartialEq + Clone> Analyser<T> for Lexer<'_, T> {
+    /// Get the current position of cursor within the sequence
     ///
     /// # Returns
-    /// The sequence being analyzed as an owned vector
-    fn drain(self) -> Vec<T> { self.contents }
+    /// Cursor position as usize
+    fn pos(&self) -> usize { self.cursor }
 
     /// Sets the cursor to a given position
     ///
@@ -114,6 +579,59 @@ impl<T: Sized + PartialEq + Copy> Analyser<T> for Lexer<T> {
     /// * `position: usize` - The index in sequence, where cursor will be placed
     ///
     /// # Returns
-    /// `std::io::Result<()>` - Ok if operation successful, otherwise an Err with the `std::io::Error`
-    fn set_pos(&mut self, position: usize) -> io::Result<()> { Ok(self.cursor = position) }
-}
\ No newline at end of file
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError` if the position is past the end
+    fn set_pos(&mut self, position: usize) -> Result<()> {
+        if position > self.contents.len() {
+            return Err(BexError::CursorOutOfBounds { pos: position, len: self.contents.len() })
+        }
+        self.cursor = position;
+        Ok(())
+    }
+
+    /// Looks at the current element in the sequence without moving the cursor.
+    ///
+    /// # Returns
+    /// `Result<&T>` - Ok with a reference to the current element, otherwise an `UnexpectedEof` error
+    fn peek(&self) -> Result<&T> { peek_contents(&self.contents, self.cursor) }
+
+    /// Check if end of sequence is reached by the cursor
+    ///
+    /// # Returns
+    /// Boolean that's true if end of sequence has been reached
+    fn is_end(&self) -> bool { self.cursor >= self.contents.len() }
+
+    /// Saves the cursor position and the mode stack so they can be restored later
+    ///
+    /// # Returns
+    /// A `Checkpoint` to pass to `rollback`
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint::with_modes(self.cursor, self.modes.as_ref().map(|it| it.clone_boxed()))
+    }
+
+    /// Moves the cursor back to a saved position and restores the mode stack saved with it
+    ///
+    /// # Arguments
+    /// * `checkpoint` - The position saved by `checkpoint`
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError`
+    fn rollback(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        self.set_pos(checkpoint.pos())?;
+        self.modes = checkpoint.into_modes();
+        Ok(())
+    }
+}
+
+impl<T: Sized + PartialEq + Clone> SliceAnalyser<T> for Lexer<'_, T> {
+    /// Get the entire sequence being analyzed
+    ///
+    /// # Returns
+    /// Array slice of the sequence being analyzed
+    fn contents(&self) -> &[T] { &self.contents[..] }
+
+    /// Consumes the analyser, returning the sequence being analyzed
+    ///
+    /// # Returns
+    /// The sequence being analyzed as an owned vector
+    fn drain(self) -> Vec<T> { self.contents.into_owned() }
+}
@@ -1,4 +1,22 @@
+pub mod error; pub use error::*;
 pub mod read; pub use read::*;
+pub mod search; pub use search::*;
+pub mod utf8; pub use utf8::*;
+pub mod stream; pub use stream::*;
+pub mod location; pub use location::*;
+pub mod edit; pub use edit::*;
+pub mod mode; pub use mode::*;
 pub mod lexer; pub use lexer::*;
+pub mod tokens; pub use tokens::*;
+pub mod trivia; pub use trivia::*;
+pub mod vfs; pub use vfs::*;
+pub mod source_db; pub use source_db::*;
+pub mod diagnostic; pub use diagnostic::*;
+pub mod source_map; pub use source_map::*;
+pub mod cst; pub use cst::*;
+pub mod combinator; pub use combinator::*;
 pub mod parse; pub use parse::*;
-pub mod process; pub use process::*;
\ No newline at end of file
+pub mod process; pub use process::*;
+pub mod preprocessor; pub use preprocessor::*;
+#[cfg(feature = "derive")]
+pub use bex_derive::Token;
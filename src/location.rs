@@ -0,0 +1,160 @@
+use std::{fmt, ops};
+
+/// A zero-based line and column within a sequence
+///
+/// Columns are counted in characters, so a multibyte UTF-8 character occupies a single column.
+/// The `Display` implementation prints the one-based `line:column` form used in diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct LineCol {
+    pub line:   usize,
+    pub column: usize
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
+}
+
+/// A value together with the range of the sequence it was read from
+///
+/// # Type Parameters
+/// * `T` - The spanned value, usually a token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span:  ops::Range<usize>
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: ops::Range<usize>) -> Self { Self { value, span } }
+
+    /// Transforms the value while keeping its span
+    ///
+    /// # Arguments
+    /// * `f` - Function applied to the value
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Spanned<U> { Spanned::new(f(self.value), self.span) }
+
+    /// Borrows the value while keeping its span
+    pub fn as_ref(&self) -> Spanned<&T> { Spanned::new(&self.value, self.span.clone()) }
+}
+
+/// Defines how elements of a sequence contribute to lines and columns
+pub trait LineSource: Sized + PartialEq + Copy {
+    /// Check if the element ends a line
+    fn is_line_feed(&self) -> bool;
+
+    /// Check if the element is a carriage return, which belongs to the line ending when followed by a line feed
+    fn is_carriage_return(&self) -> bool;
+
+    /// Check if the element continues the previous character instead of starting a new column
+    fn is_continuation(&self) -> bool;
+}
+
+impl LineSource for u8 {
+    fn is_line_feed(&self) -> bool { *self == b'\n' }
+
+    fn is_carriage_return(&self) -> bool { *self == b'\r' }
+
+    fn is_continuation(&self) -> bool { *self & 0xC0 == 0x80 }
+}
+
+impl LineSource for char {
+    fn is_line_feed(&self) -> bool { *self == '\n' }
+
+    fn is_carriage_return(&self) -> bool { *self == '\r' }
+
+    fn is_continuation(&self) -> bool { false }
+}
+
+/// Converts offsets within a sequence into lines and columns and back
+///
+/// `\n` and `\r\n` both end a line, the `\r` of a `\r\n` pair is treated as part of the line ending.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts:   Vec<usize>,
+    line_ends:     Vec<usize>,
+    continuations: Vec<usize>,
+    len:           usize
+}
+
+impl LineIndex {
+    /// Builds a line index over the given sequence
+    ///
+    /// # Arguments
+    /// * `contents` - The sequence to index
+    pub fn new<T: LineSource>(contents: &[T]) -> Self {
+        let mut line_starts = vec![0];
+        let mut line_ends = vec![];
+        let mut continuations = vec![];
+        for (offset, element) in contents.iter().enumerate() {
+            if element.is_continuation() {
+                continuations.push(offset);
+            } else if element.is_line_feed() {
+                let crlf = offset > 0 && contents[offset - 1].is_carriage_return();
+                line_ends.push(if crlf { offset - 1 } else { offset });
+                line_starts.push(offset + 1);
+            }
+        }
+        line_ends.push(contents.len());
+        Self { line_starts, line_ends, continuations, len: contents.len() }
+    }
+
+    /// Get the number of lines in the sequence
+    ///
+    /// # Returns
+    /// Line count, an empty sequence has a single empty line
+    pub fn line_count(&self) -> usize { self.line_starts.len() }
+
+    /// Get the range of a line, excluding its line ending
+    ///
+    /// # Arguments
+    /// * `line` - Zero-based line number
+    ///
+    /// # Returns
+    /// `Some` with the offsets of the line, `None` if the line does not exist
+    pub fn line_range(&self, line: usize) -> Option<ops::Range<usize>> {
+        Some(*self.line_starts.get(line)?..self.line_ends[line])
+    }
+
+    /// Converts an offset into a line and column
+    ///
+    /// # Arguments
+    /// * `offset` - Offset within the sequence, the length of the sequence is a valid end position
+    ///
+    /// # Returns
+    /// `Some` with the location of the offset, `None` if the offset is out of bounds
+    pub fn line_col(&self, offset: usize) -> Option<LineCol> {
+        if offset > self.len { return None }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let column = offset.min(self.line_ends[line]) - start - self.continuations_between(start, offset.min(self.line_ends[line]));
+        Some(LineCol { line, column })
+    }
+
+    /// Converts a line and column back into an offset
+    ///
+    /// # Arguments
+    /// * `location` - The location to convert, a column at the end of the line is valid
+    ///
+    /// # Returns
+    /// `Some` with the offset of the location, `None` if the line or column does not exist
+    pub fn offset(&self, location: LineCol) -> Option<usize> {
+        let range = self.line_range(location.line)?;
+        let mut offset = range.start;
+        let mut column = 0;
+        while column < location.column {
+            if offset >= range.end { return None }
+            offset += 1;
+            while offset < range.end && self.continuations.binary_search(&offset).is_ok() {
+                offset += 1;
+            }
+            column += 1;
+        }
+        Some(offset)
+    }
+
+    fn continuations_between(&self, start: usize, end: usize) -> usize {
+        self.continuations.partition_point(|&it| it < end) - self.continuations.partition_point(|&it| it < start)
+    }
+}
@@ -0,0 +1,86 @@
+use std::any::Any;
+
+/// A stack of lexer modes, such as "inside string" or "inside preprocessor directive"
+///
+/// The bottom of the stack is the base mode created by `Default` and is never popped, so there is
+/// always a current mode. A `Lexer` keeps the stack between tokens, see `ScopedToken`.
+///
+/// # Type Parameters
+/// * `S` - The type of the modes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeStack<S> {
+    modes: Vec<S>
+}
+
+impl<S: Default> Default for ModeStack<S> {
+    fn default() -> Self { Self::new(S::default()) }
+}
+
+impl<S> ModeStack<S> {
+    /// Creates a stack with the given base mode
+    ///
+    /// # Arguments
+    /// * `base` - The mode at the bottom of the stack
+    pub fn new(base: S) -> Self { Self { modes: vec![base] } }
+
+    /// Get the mode on top of the stack
+    pub fn current(&self) -> &S { &self.modes[self.modes.len() - 1] }
+
+    /// Get the mode on top of the stack mutably, for modes which carry state
+    pub fn current_mut(&mut self) -> &mut S {
+        let top = self.modes.len() - 1;
+        &mut self.modes[top]
+    }
+
+    /// Enters a mode
+    ///
+    /// # Arguments
+    /// * `mode` - The mode to push on top of the stack
+    pub fn push(&mut self, mode: S) { self.modes.push(mode) }
+
+    /// Leaves the current mode
+    ///
+    /// # Returns
+    /// `Some` with the mode that was left, `None` if only the base mode is left
+    pub fn pop(&mut self) -> Option<S> {
+        match self.modes.len() {
+            1 => None,
+            _ => self.modes.pop()
+        }
+    }
+
+    /// Get the number of modes pushed on top of the base mode
+    pub fn depth(&self) -> usize { self.modes.len() - 1 }
+
+    /// Check if only the base mode is left
+    pub fn is_base(&self) -> bool { self.modes.len() == 1 }
+
+    /// Pops every mode except the base mode
+    pub fn reset(&mut self) { self.modes.truncate(1) }
+
+    /// Iterates over the modes from the base mode to the current one
+    pub fn iter(&self) -> impl Iterator<Item = &S> { self.modes.iter() }
+}
+
+/// A `ModeStack` of any mode type, which a `Lexer` keeps and clones into its checkpoints.
+///
+/// `Send` is required so the lexer holding it stays `Send`.
+pub(crate) trait ErasedModes: Send {
+    fn clone_boxed(&self) -> Box<dyn ErasedModes>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<S: Clone + Send + 'static> ErasedModes for ModeStack<S> {
+    fn clone_boxed(&self) -> Box<dyn ErasedModes> { Box::new(self.clone()) }
+
+    fn as_any(&self) -> &dyn Any { self }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any { self }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> { self }
+}
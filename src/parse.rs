@@ -1,13 +1,17 @@
 use std::fmt::Debug;
-use std::io;
+use crate::error::BexError;
 use crate::lexer::{Lexer, Token};
+use crate::read::Analyser;
+use crate::source_db::{FileId, SourceDb};
+use crate::source_map::{HasSpan, Located, Preprocessed};
+use crate::tokens::TokenStream;
 
 /// The `Parse` trait defines the methods required to parse the lexers content or tokens
 ///
 /// # Type Parameters
 /// * `T` - Any type that is Sized (has a constant size in memory), and can be compared for equality.
-pub trait Parse<T: Sized + PartialEq + Copy>: Sized {
-    type E: From<io::Error> + Debug;
+pub trait Parse<T: Sized + PartialEq + Clone>: Sized {
+    type E: From<BexError> + Debug;
 
     /// Parses the given file using the given lexer and returns the parser.
     /// This method will panic in case of any errors during parsing.
@@ -16,7 +20,7 @@ pub trait Parse<T: Sized + PartialEq + Copy>: Sized {
     ///
     /// * `filename` - The input file to parse
     /// * `lexer` - The lexer to use for parsing
-    fn parse(filename: String, lexer: &mut Lexer<T>) -> Self { Self::try_parse(filename, lexer).unwrap() }
+    fn parse(filename: String, lexer: &mut Lexer<'_, T>) -> Self { Self::try_parse(filename, lexer).unwrap() }
 
     /// Attempts to parse the given file using the given lexer and returns the parser or an error.
     ///
@@ -24,5 +28,200 @@ pub trait Parse<T: Sized + PartialEq + Copy>: Sized {
     ///
     /// * `filename` - The input file to parse
     /// * `lexer` - The lexer to use for parsing
-    fn try_parse(filename: String, lexer: &mut Lexer<T>) -> Result<Self, Self::E>;
+    fn try_parse(filename: String, lexer: &mut Lexer<'_, T>) -> Result<Self, Self::E>;
+
+    /// Attempts to parse a file of a `SourceDb`, the lexer is tagged with the id of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - The database owning the file
+    /// * `file` - The id of the file to parse
+    fn try_parse_file(sources: &SourceDb<T>, file: FileId) -> Result<Self, Self::E> {
+        let source = &sources[file];
+        Self::try_parse(source.name().to_string(), &mut source.lexer())
+    }
+
+    /// Attempts to parse preprocessed output, resolving errors back to the original source.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The file that was preprocessed
+    /// * `preprocessed` - The output of a `PreProcess` implementation
+    fn try_parse_preprocessed(
+        filename: String,
+        preprocessed: &Preprocessed<T>
+    ) -> Result<Self, Located<Self::E>> where Self::E: HasSpan {
+        Self::try_parse(filename, &mut preprocessed.lexer())
+            .map_err(|e| Located::resolve(e, &preprocessed.source_map))
+    }
+}
+
+/// Sources of tokens for `PrattParser`, implemented for a `Lexer` over tokens and for `TokenStream`
+///
+/// # Type Parameters
+/// * `T` - The type of the tokens.
+pub trait TokenSource<T> {
+    type Error: From<BexError>;
+
+    /// Looks at the next token without consuming it
+    ///
+    /// # Returns
+    /// `Ok(None)` at the end of the input
+    fn peek_token(&mut self) -> Result<Option<T>, Self::Error>;
+
+    /// Consumes the next token
+    fn bump(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<T: Sized + PartialEq + Clone> TokenSource<T> for Lexer<'_, T> {
+    type Error = BexError;
+
+    fn peek_token(&mut self) -> Result<Option<T>, Self::Error> {
+        match self.is_end() {
+            true => Ok(None),
+            false => self.peek().map(|it| Some(it.clone()))
+        }
+    }
+
+    fn bump(&mut self) -> Result<(), Self::Error> { self.step_forward() }
+}
+
+impl<'a, TokenType: Token<'a, T> + Clone, T: Sized + PartialEq + Clone> TokenSource<TokenType> for TokenStream<'a, TokenType, T> {
+    type Error = TokenType::Error;
+
+    fn peek_token(&mut self) -> Result<Option<TokenType>, Self::Error> {
+        match self.peek() {
+            None => Ok(None),
+            Some(Ok(token)) => Ok(Some(token.clone())),
+            Some(Err(_)) => Err(self.next().and_then(Result::err).expect("peeked an error"))
+        }
+    }
+
+    fn bump(&mut self) -> Result<(), Self::Error> {
+        self.next().transpose().map(|_| ())
+    }
+}
+
+/// How operators of the same precedence group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`
+    Right
+}
+
+type Prefix<T, O> = (T, u16, Box<dyn Fn(T, O) -> O>);
+type Infix<T, O> = (T, u16, u16, Box<dyn Fn(O, T, O) -> O>);
+type Postfix<T, O> = (T, u16, Box<dyn Fn(O, T) -> O>);
+
+/// Operator precedence parser configured with prefix, infix and postfix operator tables
+///
+/// Operators with a higher precedence bind tighter. Every operator has a callback building the
+/// node of an application of it, and operands are parsed by the callback given to `parse`.
+/// A token may be both a prefix and an infix operator, such as `-`. The operand of a prefix
+/// operator stops before infix operators of the same precedence, so `-a - b` is `(-a) - b`. A token
+/// which is both a postfix and an infix operator is read as postfix if its postfix precedence binds
+/// tightly enough to continue the expression, and as infix otherwise.
+///
+/// # Type Parameters
+/// * `T` - The type of the tokens.
+/// * `O` - The type of the nodes being built.
+pub struct PrattParser<T, O> {
+    prefix:  Vec<Prefix<T, O>>,
+    infix:   Vec<Infix<T, O>>,
+    postfix: Vec<Postfix<T, O>>
+}
+
+impl<T, O> Default for PrattParser<T, O> {
+    fn default() -> Self { Self { prefix: vec![], infix: vec![], postfix: vec![] } }
+}
+
+impl<T: PartialEq + Clone, O> PrattParser<T, O> {
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds a prefix operator
+    ///
+    /// # Arguments
+    /// * `operator` - The operator token
+    /// * `precedence` - How tightly the operator binds its operand
+    /// * `build` - Builds the node from the operator and its operand
+    pub fn prefix<F: Fn(T, O) -> O + 'static>(mut self, operator: T, precedence: u8, build: F) -> Self {
+        self.prefix.push((operator, 2 * precedence as u16 + 2, Box::new(build)));
+        self
+    }
+
+    /// Adds an infix operator
+    ///
+    /// # Arguments
+    /// * `operator` - The operator token
+    /// * `precedence` - How tightly the operator binds its operands
+    /// * `associativity` - How chains of operators of the same precedence group
+    /// * `build` - Builds the node from the left operand, the operator and the right operand
+    pub fn infix<F: Fn(O, T, O) -> O + 'static>(mut self, operator: T, precedence: u8, associativity: Associativity, build: F) -> Self {
+        let power = 2 * precedence as u16 + 1;
+        let (left, right) = match associativity {
+            Associativity::Left => (power, power + 1),
+            Associativity::Right => (power + 1, power)
+        };
+        self.infix.push((operator, left, right, Box::new(build)));
+        self
+    }
+
+    /// Adds a postfix operator
+    ///
+    /// # Arguments
+    /// * `operator` - The operator token
+    /// * `precedence` - How tightly the operator binds its operand
+    /// * `build` - Builds the node from the operand and the operator
+    pub fn postfix<F: Fn(O, T) -> O + 'static>(mut self, operator: T, precedence: u8, build: F) -> Self {
+        self.postfix.push((operator, 2 * precedence as u16 + 1, Box::new(build)));
+        self
+    }
+
+    /// Parses an expression, stopping before the first token which is not an operator continuing it
+    ///
+    /// # Arguments
+    /// * `input` - The source of the tokens
+    /// * `operand` - Parses an operand, such as a literal, identifier or bracketed expression
+    ///
+    /// # Returns
+    /// The node of the expression, or the first error of the input or of `operand`
+    pub fn parse<S: TokenSource<T>, F: FnMut(&mut S) -> Result<O, S::Error>>(&self, input: &mut S, mut operand: F) -> Result<O, S::Error> {
+        self.parse_power(input, &mut operand, 0)
+    }
+
+    /// Parses an expression whose operators bind at least as tightly as `min_power`.
+    fn parse_power<S: TokenSource<T>, F: FnMut(&mut S) -> Result<O, S::Error>>(
+        &self,
+        input: &mut S,
+        operand: &mut F,
+        min_power: u16
+    ) -> Result<O, S::Error> {
+        let prefix = match input.peek_token()? {
+            Some(token) => self.prefix.iter().find(|(operator, ..)| *operator == token),
+            None => None
+        };
+        let mut left = match prefix {
+            Some((operator, power, build)) => {
+                input.bump()?;
+                let right = self.parse_power(input, operand, *power)?;
+                build(operator.clone(), right)
+            }
+            None => operand(input)?
+        };
+        while let Some(token) = input.peek_token()? {
+            if let Some((operator, _, build)) = self.postfix.iter().find(|(operator, power, _)| *operator == token && *power >= min_power) {
+                input.bump()?;
+                left = build(left, operator.clone());
+            } else if let Some((operator, _, right_power, build)) = self.infix.iter().find(|(operator, power, ..)| *operator == token && *power >= min_power) {
+                input.bump()?;
+                let right = self.parse_power(input, operand, *right_power)?;
+                left = build(left, operator.clone(), right);
+            } else {
+                break
+            }
+        }
+        Ok(left)
+    }
 }
@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::{error, fmt, ops};
+use crate::diagnostic::{Diagnostic, Label, ToDiagnostic};
+use crate::error::BexError;
+use crate::lexer::Lexer;
+use crate::process::PreProcess;
+use crate::read::{Analyser, SliceAnalyser};
+use crate::search::ByteSearch;
+use crate::source_db::{FileId, FileSpan, SourceDb, SourceFile};
+use crate::source_map::{Expansion, Origin, Preprocessed, SourceMap};
+use crate::vfs::{DiskFileSystem, IncludeResolver};
+
+/// Maximum depth of nested `#include` directives before preprocessing is aborted
+pub const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// The reasons preprocessing can fail
+#[derive(Debug)]
+pub enum PreprocessErrorKind {
+    /// Reading the input failed
+    Bex(BexError),
+    /// A `#` at the start of a line was followed by an unknown directive
+    UnknownDirective(String),
+    /// A directive was missing its arguments or had malformed ones
+    InvalidDirective(String),
+    /// An `#else` or `#endif` appeared without a matching `#ifdef`, `#ifndef` or `#if`
+    UnmatchedConditional(String),
+    /// A file ended inside a conditional block
+    UnterminatedConditional,
+    /// A block comment was not closed before the end of the file
+    UnterminatedComment,
+    /// The arguments of a macro invocation were not closed before the end of the file
+    UnterminatedArguments(String),
+    /// A macro was invoked with the wrong number of arguments
+    ArgumentCount { name: String, expected: usize, found: usize },
+    /// An included file could not be loaded
+    Include { path: String, error: Box<BexError> },
+    /// Includes were nested deeper than `MAX_INCLUDE_DEPTH`
+    IncludeDepth(String)
+}
+
+/// An error raised by `BohemiaPreProcessor`, with the file and range it was raised at
+#[derive(Debug)]
+pub struct PreprocessError {
+    pub kind: PreprocessErrorKind,
+    pub file: Option<Arc<str>>,
+    pub span: Option<FileSpan>
+}
+
+impl PreprocessError {
+    fn new(kind: PreprocessErrorKind, file: &SourceFile<u8>, range: ops::Range<usize>) -> Self {
+        Self { kind, file: Some(file.name().clone()), span: Some(file.span(range)) }
+    }
+
+    /// Attributes an error raised while lexing macro text, whose offsets are not offsets of the
+    /// file, to the call site of the macro
+    fn at_call_site(mut self, file: &SourceFile<u8>, call_site: ops::Range<usize>) -> Self {
+        self.file = Some(file.name().clone());
+        self.span = Some(file.span(call_site));
+        self
+    }
+
+    /// Attributes an error raised by the lexer of a file to that file
+    fn in_file(mut self, file: &SourceFile<u8>) -> Self {
+        if self.file.is_none() {
+            if let PreprocessErrorKind::Bex(e) = &self.kind { self.span = e.span().map(|it| file.span(it)); }
+            self.file = Some(file.name().clone());
+        }
+        self
+    }
+}
+
+impl PreprocessErrorKind {
+    /// Get the code identifying the kind of error in diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Bex(_) => "read",
+            Self::UnknownDirective(_) => "unknown-directive",
+            Self::InvalidDirective(_) => "invalid-directive",
+            Self::UnmatchedConditional(_) => "unmatched-conditional",
+            Self::UnterminatedConditional => "unterminated-conditional",
+            Self::UnterminatedComment => "unterminated-comment",
+            Self::UnterminatedArguments(_) => "unterminated-arguments",
+            Self::ArgumentCount { .. } => "argument-count",
+            Self::Include { .. } => "include",
+            Self::IncludeDepth(_) => "include-depth"
+        }
+    }
+}
+
+impl fmt::Display for PreprocessErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bex(e) => write!(f, "{e}"),
+            Self::UnknownDirective(name) => write!(f, "Unknown directive #{name}."),
+            Self::InvalidDirective(name) => write!(f, "Malformed #{name} directive."),
+            Self::UnmatchedConditional(name) => write!(f, "#{name} without a matching #ifdef, #ifndef or #if."),
+            Self::UnterminatedConditional => write!(f, "Conditional block is not closed by #endif."),
+            Self::UnterminatedComment => write!(f, "Block comment is not closed."),
+            Self::UnterminatedArguments(name) => write!(f, "Arguments of {name} are not closed."),
+            Self::ArgumentCount { name, expected, found } =>
+                write!(f, "{name} takes {expected} arguments but {found} were given."),
+            Self::Include { path, error } => write!(f, "Could not include {path}: {error}"),
+            Self::IncludeDepth(path) => write!(f, "Including {path} exceeds the maximum include depth."),
+        }
+    }
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{file}")?;
+            if let Some(span) = &self.span { write!(f, "@{}", span.range.start)?; }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl ToDiagnostic for PreprocessError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(self.kind.to_string()).with_code(self.kind.code());
+        if let Some(span) = &self.span {
+            diagnostic = diagnostic.with_label(Label::primary(span.clone(), ""));
+        }
+        match &self.kind {
+            PreprocessErrorKind::UnterminatedConditional => diagnostic.with_help("close the block with #endif"),
+            PreprocessErrorKind::UnterminatedComment => diagnostic.with_help("close the comment with */"),
+            PreprocessErrorKind::IncludeDepth(_) =>
+                diagnostic.with_note(format!("includes may be nested at most {MAX_INCLUDE_DEPTH} deep, check for a file including itself")),
+            _ => diagnostic
+        }
+    }
+}
+
+impl error::Error for PreprocessError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            PreprocessErrorKind::Bex(e) => Some(e),
+            PreprocessErrorKind::Include { error, .. } => Some(error.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl From<BexError> for PreprocessError {
+    fn from(e: BexError) -> Self {
+        Self { kind: PreprocessErrorKind::Bex(e), file: None, span: None }
+    }
+}
+
+type Result<T> = std::result::Result<T, PreprocessError>;
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Option<Vec<Vec<u8>>>,
+    body:   Vec<u8>
+}
+
+/// A lexical piece of preprocessor input
+enum Piece {
+    Newline,
+    Whitespace,
+    Identifier,
+    String,
+    LineComment,
+    BlockComment,
+    Other
+}
+
+#[derive(Debug)]
+struct Conditional {
+    start:         usize,
+    parent_active: bool,
+    active:        bool,
+    else_seen:     bool
+}
+
+/// Preprocessor compatible with the one used by Arma and DayZ for config and script files
+///
+/// Supports `#define` with and without arguments, `#undef`, `#ifdef`, `#ifndef`, `#if`, `#else`,
+/// `#endif` and `#include`, `#` stringification, `##` token pasting, line continuations in
+/// directives and comment stripping. As in Bohemia's tools, macro arguments are expanded before
+/// they are substituted, stringified or pasted, and macros are not expanded inside `"` strings.
+/// Stringification doubles embedded quotes, the escape used by Bohemia's strings.
+pub struct BohemiaPreProcessor {
+    defines:  HashMap<Vec<u8>, Macro>,
+    resolver: Box<dyn IncludeResolver>,
+    sources:  SourceDb,
+    depth:    usize
+}
+
+impl Default for BohemiaPreProcessor {
+    fn default() -> Self { Self::new() }
+}
+
+impl BohemiaPreProcessor {
+    /// Creates a preprocessor which loads includes from disk, relative to the working directory
+    pub fn new() -> Self {
+        Self::with_resolver(DiskFileSystem::new().with_search_path("."))
+    }
+
+    /// Creates a preprocessor which loads includes through the given resolver
+    ///
+    /// # Arguments
+    /// * `resolver` - Resolves and reads included files, such as a `MemoryFileSystem`
+    pub fn with_resolver<R: IncludeResolver + 'static>(resolver: R) -> Self {
+        Self { defines: HashMap::new(), resolver: Box::new(resolver), sources: SourceDb::new(), depth: 0 }
+    }
+
+    /// Defines an object-like macro, as if by `#define name body`
+    ///
+    /// # Arguments
+    /// * `name` - Name of the macro
+    /// * `body` - Text the macro expands to
+    pub fn define(&mut self, name: &str, body: &str) {
+        self.defines.insert(name.as_bytes().to_vec(), Macro { params: None, body: body.as_bytes().to_vec() });
+    }
+
+    /// Removes a macro, as if by `#undef name`
+    ///
+    /// # Arguments
+    /// * `name` - Name of the macro
+    pub fn undefine(&mut self, name: &str) { self.defines.remove(name.as_bytes()); }
+
+    /// Check if a macro is defined
+    ///
+    /// # Arguments
+    /// * `name` - Name of the macro
+    pub fn is_defined(&self, name: &str) -> bool { self.defines.contains_key(name.as_bytes()) }
+
+    /// Get the files read so far, including every file that was included
+    pub fn sources(&self) -> &SourceDb { &self.sources }
+
+    /// Get the files read so far, so files can be added before they are preprocessed
+    pub fn sources_mut(&mut self) -> &mut SourceDb { &mut self.sources }
+
+    /// Preprocesses a file which was added to `sources`, expanding includes and macros
+    ///
+    /// Included files are loaded into `sources` once and reused by later calls.
+    ///
+    /// # Arguments
+    /// * `file` - Id of the file in `sources`, panics if it was handed out by another `SourceDb`
+    ///
+    /// # Returns
+    /// The preprocessed output together with a `SourceMap` back to the original files
+    pub fn preprocess_file(&mut self, file: FileId) -> std::result::Result<Preprocessed<u8>, PreprocessError> {
+        self.preprocess_from(file, 0)
+    }
+
+    fn preprocess_from(&mut self, file: FileId, start: usize) -> Result<Preprocessed<u8>> {
+        let source = self.sources[file].clone();
+        let mut output = Output { bytes: vec![], source_map: SourceMap::new() };
+        self.process_file(&source, start, &mut output)?;
+        Ok(Preprocessed { output: output.bytes, source_map: output.source_map })
+    }
+
+    fn process_file(&mut self, source: &SourceFile<u8>, start: usize, output: &mut Output) -> Result<()> {
+        output.source_map.add_file(source);
+        self.process_lines(source, start, output).map_err(|e| e.in_file(source))
+    }
+
+    fn process_lines(&mut self, source: &SourceFile<u8>, start: usize, output: &mut Output) -> Result<()> {
+        let mut lexer = source.lexer();
+        let lexer = &mut lexer;
+        lexer.set_pos(start)?;
+        let mut conditionals: Vec<Conditional> = vec![];
+        while !lexer.is_end() {
+            let line_start = lexer.pos();
+            lexer.skip_while(|it| *it == b' ' || *it == b'\t')?;
+            let active = conditionals.last().is_none_or(|it| it.active);
+            if eat(lexer, b'#') {
+                self.directive(source, lexer, line_start, &mut conditionals, output)?;
+            } else if active {
+                output.copy(source.id(), line_start..lexer.pos(), &source.contents()[line_start..lexer.pos()]);
+                self.text_line(source, lexer, output)?;
+            } else {
+                skip_line(source, lexer, output)?;
+            }
+        }
+        match conditionals.first() {
+            Some(conditional) =>
+                Err(PreprocessError::new(PreprocessErrorKind::UnterminatedConditional, source, conditional.start..conditional.start)),
+            None => Ok(())
+        }
+    }
+
+    /// Copies and expands text up to and including the next line break outside of comments and arguments.
+    fn text_line(&mut self, source: &SourceFile<u8>, lexer: &mut Lexer<'_, u8>, output: &mut Output) -> Result<()> {
+        while !lexer.is_end() {
+            let start = lexer.pos();
+            let piece = next_piece(source, lexer)?;
+            let range = start..lexer.pos();
+            match piece {
+                Piece::Newline => {
+                    output.copy(source.id(), range.clone(), &source.contents()[range]);
+                    return Ok(())
+                }
+                Piece::LineComment => {}
+                Piece::BlockComment => output.newlines_of(source.id(), range, source.contents()),
+                Piece::Identifier => self.identifier(source, lexer, range, output)?,
+                _ => output.copy(source.id(), range.clone(), &source.contents()[range])
+            }
+        }
+        Ok(())
+    }
+
+    fn identifier(&self, source: &SourceFile<u8>, lexer: &mut Lexer<'_, u8>, range: ops::Range<usize>, output: &mut Output) -> Result<()> {
+        let name = &source.contents()[range.clone()];
+        let context = Context { source, offset: range.start };
+        if let Some(builtin) = context.builtin(name) {
+            let origin = Origin::verbatim(source.span(range.clone()));
+            output.expansion(&builtin, origin);
+            return Ok(())
+        }
+        let Some(definition) = self.defines.get(name) else {
+            output.copy(source.id(), range.clone(), name);
+            return Ok(())
+        };
+        let arguments = match definition.params {
+            None => vec![],
+            Some(_) => match read_arguments(source, lexer, name)? {
+                Some(arguments) => arguments,
+                None => {
+                    output.copy(source.id(), range.clone(), name);
+                    return Ok(())
+                }
+            }
+        };
+        let call_site = range.start..lexer.pos();
+        let mut expanded = vec![];
+        let text = self.expand_macro(&context, name, arguments, &mut vec![], &mut expanded, call_site.clone())?;
+        let expansions = expanded.into_iter()
+            .map(|name| Expansion { name, call_site: source.span(call_site.clone()) })
+            .collect();
+        output.expansion(&text, Origin { span: source.span(call_site), expansions });
+        Ok(())
+    }
+
+    fn expand_macro(
+        &self,
+        context: &Context,
+        name: &[u8],
+        arguments: Vec<Vec<u8>>,
+        disabled: &mut Vec<Vec<u8>>,
+        expanded: &mut Vec<String>,
+        call_site: ops::Range<usize>
+    ) -> Result<Vec<u8>> {
+        let definition = &self.defines[name];
+        let params = definition.params.as_deref().unwrap_or_default();
+        let arguments = match (params.len(), arguments.len()) {
+            (0, 1) if arguments[0].iter().all(u8::is_ascii_whitespace) => vec![],
+            (expected, found) if expected != found => return Err(PreprocessError::new(
+                PreprocessErrorKind::ArgumentCount { name: String::from_utf8_lossy(name).into_owned(), expected, found },
+                context.source,
+                call_site
+            )),
+            _ => arguments
+        };
+        expanded.push(String::from_utf8_lossy(name).into_owned());
+        let arguments = arguments.iter()
+            .map(|it| self.expand_text(context, it, disabled, expanded, call_site.clone()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut substituted = vec![];
+        let mut body = Lexer::from_slice(&definition.body);
+        while !body.is_end() {
+            let start = body.pos();
+            let piece = next_piece(context.source, &mut body).map_err(|e| e.at_call_site(context.source, call_site.clone()))?;
+            let text = &definition.body[start..body.pos()];
+            match piece {
+                Piece::Identifier => match params.iter().position(|it| it == text) {
+                    Some(index) => substituted.extend_from_slice(&arguments[index]),
+                    None => substituted.extend_from_slice(text)
+                },
+                Piece::Other if text == b"#" && eat(&mut body, b'#') => {
+                    trim_end(&mut substituted);
+                    body.skip_while(|it| *it == b' ' || *it == b'\t')?;
+                }
+                Piece::Other if text == b"#" => {
+                    let checkpoint = body.checkpoint();
+                    let parameter_start = body.pos();
+                    let is_identifier = matches!(
+                        next_piece(context.source, &mut body).map_err(|e| e.at_call_site(context.source, call_site.clone()))?,
+                        Piece::Identifier
+                    );
+                    let parameter = &definition.body[parameter_start..body.pos()];
+                    match params.iter().position(|it| is_identifier && it == parameter) {
+                        Some(index) => {
+                            substituted.push(b'"');
+                            for &byte in &arguments[index] {
+                                if byte == b'"' { substituted.push(b'"') }
+                                substituted.push(byte);
+                            }
+                            substituted.push(b'"');
+                        }
+                        None => {
+                            body.rollback(checkpoint)?;
+                            substituted.push(b'#');
+                        }
+                    }
+                }
+                _ => substituted.extend_from_slice(text)
+            }
+        }
+
+        disabled.push(name.to_vec());
+        let result = self.expand_text(context, &substituted, disabled, expanded, call_site);
+        disabled.pop();
+        result
+    }
+
+    /// Expands every macro invocation within text produced by an expansion.
+    fn expand_text(
+        &self,
+        context: &Context,
+        text: &[u8],
+        disabled: &mut Vec<Vec<u8>>,
+        expanded: &mut Vec<String>,
+        call_site: ops::Range<usize>
+    ) -> Result<Vec<u8>> {
+        let mut result = vec![];
+        let mut lexer = Lexer::from_slice(text);
+        while !lexer.is_end() {
+            let start = lexer.pos();
+            let piece = next_piece(context.source, &mut lexer).map_err(|e| e.at_call_site(context.source, call_site.clone()))?;
+            let name = &text[start..lexer.pos()];
+            if !matches!(piece, Piece::Identifier) || disabled.iter().any(|it| it == name) {
+                result.extend_from_slice(name);
+                continue
+            }
+            if let Some(builtin) = context.builtin(name) {
+                result.extend_from_slice(&builtin);
+                continue
+            }
+            let Some(definition) = self.defines.get(name) else {
+                result.extend_from_slice(name);
+                continue
+            };
+            let arguments = match definition.params {
+                None => vec![],
+                Some(_) => match read_arguments(context.source, &mut lexer, name).map_err(|e| e.at_call_site(context.source, call_site.clone()))? {
+                    Some(arguments) => arguments,
+                    None => {
+                        result.extend_from_slice(name);
+                        continue
+                    }
+                }
+            };
+            result.extend(self.expand_macro(context, name, arguments, disabled, expanded, call_site.clone())?);
+        }
+        Ok(result)
+    }
+
+    fn directive(
+        &mut self,
+        source: &SourceFile<u8>,
+        lexer: &mut Lexer<'_, u8>,
+        line_start: usize,
+        conditionals: &mut Vec<Conditional>,
+        output: &mut Output
+    ) -> Result<()> {
+        let (line, newlines) = read_directive(source, lexer)?;
+        let span = line_start..lexer.pos();
+        let mut directive = Lexer::from_slice(&line);
+        directive.skip_while(u8::is_ascii_whitespace)?;
+        let name = String::from_utf8_lossy(&directive.take_while(is_identifier_part)?).into_owned();
+        directive.skip_while(u8::is_ascii_whitespace)?;
+        let rest = &line[directive.pos()..];
+        let active = conditionals.last().is_none_or(|it| it.active);
+        let invalid = || PreprocessError::new(PreprocessErrorKind::InvalidDirective(name.clone()), source, span.clone());
+
+        match name.as_str() {
+            "ifdef" | "ifndef" | "if" => {
+                let condition = if !active { false } else {
+                    let identifier = identifier_of(rest).ok_or_else(invalid)?;
+                    match name.as_str() {
+                        "ifdef" => self.defines.contains_key(identifier),
+                        "ifndef" => !self.defines.contains_key(identifier),
+                        _ => {
+                            let context = Context { source, offset: line_start };
+                            let value = self.expand_text(&context, rest, &mut vec![], &mut vec![], span.clone())?;
+                            let value = String::from_utf8_lossy(&value);
+                            value.trim().parse::<i64>().is_ok_and(|it| it != 0)
+                        }
+                    }
+                };
+                conditionals.push(Conditional { start: line_start, parent_active: active, active: condition, else_seen: false });
+            }
+            "else" => {
+                let conditional = conditionals.last_mut().filter(|it| !it.else_seen).ok_or_else(||
+                    PreprocessError::new(PreprocessErrorKind::UnmatchedConditional(name.clone()), source, span.clone())
+                )?;
+                conditional.else_seen = true;
+                conditional.active = conditional.parent_active && !conditional.active;
+            }
+            "endif" => {
+                conditionals.pop().ok_or_else(||
+                    PreprocessError::new(PreprocessErrorKind::UnmatchedConditional(name.clone()), source, span.clone())
+                )?;
+            }
+            _ if !active => {}
+            "define" => {
+                let mut definition = Lexer::from_slice(rest);
+                let identifier = definition.take_while(is_identifier_part)?;
+                if identifier.is_empty() { return Err(invalid()) }
+                let params = if eat(&mut definition, b'(') {
+                    let params = String::from_utf8_lossy(&definition.get_until_byte(b')').map_err(|_| invalid())?).into_owned();
+                    definition.step_forward()?;
+                    Some(params.split(',')
+                        .map(|it| it.trim().as_bytes().to_vec())
+                        .filter(|it| !it.is_empty())
+                        .collect())
+                } else { None };
+                let mut body = rest[definition.pos()..].to_vec();
+                trim_end(&mut body);
+                let start = body.iter().position(|it| !it.is_ascii_whitespace()).unwrap_or(body.len());
+                self.defines.insert(identifier, Macro { params, body: body.split_off(start) });
+            }
+            "undef" => {
+                let identifier = identifier_of(rest).ok_or_else(invalid)?;
+                self.defines.remove(identifier);
+            }
+            "include" => {
+                let path = match rest.first() {
+                    Some(b'"') => rest[1..].split(|it| *it == b'"').next(),
+                    Some(b'<') => rest[1..].split(|it| *it == b'>').next(),
+                    _ => None
+                }.ok_or_else(invalid)?;
+                let path = String::from_utf8_lossy(path).into_owned();
+                self.include(source, &path, span.clone(), output)?;
+            }
+            _ => return Err(PreprocessError::new(PreprocessErrorKind::UnknownDirective(name), source, span))
+        }
+        for newline in newlines {
+            output.copy(source.id(), newline..newline + 1, b"\n");
+        }
+        Ok(())
+    }
+
+    fn include(&mut self, from: &SourceFile<u8>, path: &str, span: ops::Range<usize>, output: &mut Output) -> Result<()> {
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(PreprocessError::new(PreprocessErrorKind::IncludeDepth(path.to_string()), from, span))
+        }
+        let file = self.sources.include(self.resolver.as_ref(), from.id(), path).map_err(|error|
+            PreprocessError::new(PreprocessErrorKind::Include { path: path.to_string(), error: Box::new(error) }, from, span.clone())
+        )?;
+        let file = self.sources[file].clone();
+        self.depth += 1;
+        let result = self.process_file(&file, 0, output);
+        self.depth -= 1;
+        result
+    }
+}
+
+impl PreProcess<u8> for BohemiaPreProcessor {
+    type E = PreprocessError;
+
+    /// Preprocesses the lexer's content from its current position, expanding includes and macros
+    ///
+    /// The content is added to `sources` under the given name, replacing a file of the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The name of the file being preprocessed, includes are resolved relative to it
+    /// * `lexer` - The lexer whose content is to be preprocessed
+    fn preprocess(&mut self, filename: String, lexer: Lexer<'_, u8>) -> std::result::Result<Preprocessed<u8>, Self::E> {
+        let start = lexer.pos();
+        let file = self.sources.add(&filename, lexer.drain());
+        self.preprocess_from(file, start)
+    }
+}
+
+/// Where a macro is being expanded, used for `__FILE__` and `__LINE__`
+///
+/// The line is only looked up when `__LINE__` is expanded.
+struct Context<'f> {
+    source: &'f SourceFile<u8>,
+    offset: usize
+}
+
+impl Context<'_> {
+    fn builtin(&self, name: &[u8]) -> Option<Vec<u8>> {
+        match name {
+            b"__FILE__" => Some(format!("\"{}\"", self.source.name()).into_bytes()),
+            b"__LINE__" => Some((self.source.line_col(self.offset).map_or(0, |it| it.line) + 1).to_string().into_bytes()),
+            _ => None
+        }
+    }
+}
+
+struct Output {
+    bytes:      Vec<u8>,
+    source_map: SourceMap
+}
+
+impl Output {
+    fn copy(&mut self, file: FileId, range: ops::Range<usize>, bytes: &[u8]) {
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        self.source_map.push(start..self.bytes.len(), Origin::verbatim(FileSpan::new(file, range)));
+    }
+
+    fn expansion(&mut self, bytes: &[u8], origin: Origin) {
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        self.source_map.push(start..self.bytes.len(), origin);
+    }
+
+    /// Keeps the line breaks of a stripped block comment so line numbers stay aligned.
+    fn newlines_of(&mut self, file: FileId, range: ops::Range<usize>, contents: &[u8]) {
+        for offset in range.filter(|it| contents[*it] == b'\n') {
+            self.copy(file, offset..offset + 1, b"\n");
+        }
+    }
+}
+
+/// Consumes the next byte if it matches, treating the end of the input as a mismatch.
+fn eat(lexer: &mut Lexer<'_, u8>, byte: u8) -> bool {
+    !lexer.is_end() && lexer.take(&byte).unwrap_or(false)
+}
+
+fn is_identifier_start(byte: &u8) -> bool { byte.is_ascii_alphabetic() || *byte == b'_' }
+
+fn is_identifier_part(byte: &u8) -> bool { byte.is_ascii_alphanumeric() || *byte == b'_' }
+
+fn identifier_of(text: &[u8]) -> Option<&[u8]> {
+    let end = text.iter().position(|it| !is_identifier_part(it)).unwrap_or(text.len());
+    Some(&text[..end]).filter(|it| !it.is_empty())
+}
+
+fn trim_end(bytes: &mut Vec<u8>) {
+    while bytes.last().is_some_and(u8::is_ascii_whitespace) {
+        bytes.pop();
+    }
+}
+
+/// Reads the next piece of input, the lexer must not be at its end.
+fn next_piece(file: &SourceFile<u8>, lexer: &mut Lexer<'_, u8>) -> Result<Piece> {
+    let start = lexer.pos();
+    let byte = lexer.get()?;
+    Ok(match byte {
+        b'\n' => Piece::Newline,
+        b'\r' if eat(lexer, b'\n') => Piece::Newline,
+        b' ' | b'\t' | b'\r' => {
+            lexer.skip_while(|it| *it == b' ' || *it == b'\t')?;
+            Piece::Whitespace
+        }
+        b'"' => {
+            loop {
+                lexer.skip_until_any_byte(b"\"\n")?;
+                if !eat(lexer, b'"') || !eat(lexer, b'"') { break }
+            }
+            Piece::String
+        }
+        b'/' if eat(lexer, b'/') => {
+            lexer.skip_until_byte(b'\n')?;
+            if lexer.contents().get(lexer.pos().wrapping_sub(1)) == Some(&b'\r') { lexer.step_back()?; }
+            Piece::LineComment
+        }
+        b'/' if eat(lexer, b'*') => {
+            lexer.seek_bytes(b"*/").map_err(|_|
+                PreprocessError::new(PreprocessErrorKind::UnterminatedComment, file, start..start + 2)
+            )?;
+            lexer.advance(2)?;
+            Piece::BlockComment
+        }
+        _ if is_identifier_start(&byte) => {
+            lexer.skip_while(is_identifier_part)?;
+            Piece::Identifier
+        }
+        _ if byte.is_ascii_digit() => {
+            lexer.skip_while(is_identifier_part)?;
+            Piece::Other
+        }
+        _ => Piece::Other
+    })
+}
+
+/// Skips a line inside an inactive conditional block, keeping only its line break.
+fn skip_line(source: &SourceFile<u8>, lexer: &mut Lexer<'_, u8>, output: &mut Output) -> Result<()> {
+    lexer.skip_until_byte(b'\n')?;
+    if eat(lexer, b'\n') {
+        let offset = lexer.pos() - 1;
+        output.copy(source.id(), offset..offset + 1, b"\n");
+    }
+    Ok(())
+}
+
+/// Reads the arguments of a function-like macro invocation, if the next non-blank character is `(`.
+fn read_arguments(file: &SourceFile<u8>, lexer: &mut Lexer<'_, u8>, name: &[u8]) -> Result<Option<Vec<Vec<u8>>>> {
+    let checkpoint = lexer.checkpoint();
+    let start = lexer.pos();
+    lexer.skip_while(|it| *it == b' ' || *it == b'\t')?;
+    if !eat(lexer, b'(') {
+        lexer.rollback(checkpoint)?;
+        return Ok(None)
+    }
+    let unterminated = || PreprocessError::new(
+        PreprocessErrorKind::UnterminatedArguments(String::from_utf8_lossy(name).into_owned()),
+        file,
+        start..start
+    );
+    let mut arguments = vec![];
+    let mut current = vec![];
+    let mut depth = 0;
+    loop {
+        if lexer.is_end() { return Err(unterminated()) }
+        let piece_start = lexer.pos();
+        let piece = next_piece(file, lexer)?;
+        let text = &lexer.contents()[piece_start..lexer.pos()];
+        match (piece, text) {
+            (Piece::LineComment | Piece::BlockComment, _) => {}
+            (Piece::Other, b"(") => {
+                depth += 1;
+                current.extend_from_slice(text);
+            }
+            (Piece::Other, b")") if depth == 0 => break,
+            (Piece::Other, b")") => {
+                depth -= 1;
+                current.extend_from_slice(text);
+            }
+            (Piece::Other, b",") if depth == 0 => arguments.push(std::mem::take(&mut current)),
+            _ => current.extend_from_slice(text)
+        }
+    }
+    arguments.push(current);
+    Ok(Some(arguments))
+}
+
+/// Reads the rest of a directive line, joining line continuations and stripping comments.
+///
+/// Returns the directive text and the offsets of every line break it consumed.
+fn read_directive(file: &SourceFile<u8>, lexer: &mut Lexer<'_, u8>) -> Result<(Vec<u8>, Vec<usize>)> {
+    let mut text = vec![];
+    let mut newlines = vec![];
+    while !lexer.is_end() {
+        let start = lexer.pos();
+        if eat(lexer, b'\\') {
+            let continuation = lexer.checkpoint();
+            eat(lexer, b'\r');
+            if eat(lexer, b'\n') {
+                newlines.push(lexer.pos() - 1);
+                continue
+            }
+            lexer.rollback(continuation)?;
+            text.push(b'\\');
+            continue
+        }
+        let piece = next_piece(file, lexer)?;
+        let range = start..lexer.pos();
+        match piece {
+            Piece::Newline => {
+                newlines.push(range.end - 1);
+                break
+            }
+            Piece::LineComment => {}
+            Piece::BlockComment => {
+                newlines.extend(range.filter(|it| lexer.contents()[*it] == b'\n'));
+                text.push(b' ');
+            }
+            _ => text.extend_from_slice(&lexer.contents()[range])
+        }
+    }
+    Ok((text, newlines))
+}
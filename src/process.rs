@@ -1,17 +1,22 @@
 use std::error::Error;
-use std::io;
-use crate::{Lexer, Token};
+use crate::error::BexError;
+use crate::source_map::Preprocessed;
+use crate::Lexer;
 
 /// The `PreProcess` trait defines the methods required to preprocess the lexers content before parsing
 ///
 /// # Type Parameters
 /// * `T` - Any type that is Sized (has a constant size in memory), and can be compared for equality.
-pub trait PreProcess<T: Sized + PartialEq + Copy> {
-    type E: Error + From<io::Error>;
+pub trait PreProcess<T: Sized + PartialEq + Clone> {
+    type E: Error + From<BexError>;
     /// Does preprocessing on the given lexer
     ///
     /// # Arguments
     ///
+    /// * `filename` - The name of the file being preprocessed, used as the origin of its text
     /// * `lexer` - The lexer whose content is to be preprocessed
-    fn preprocess(&mut self, lexer: Lexer<T>) -> Result<Vec<T>, Self::E>;
-}
\ No newline at end of file
+    ///
+    /// # Returns
+    /// The preprocessed output together with a `SourceMap` back to the original files
+    fn preprocess(&mut self, filename: String, lexer: Lexer<'_, T>) -> Result<Preprocessed<T>, Self::E>;
+}
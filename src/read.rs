@@ -1,65 +1,92 @@
-use std::{io, ops};
+use std::marker::PhantomData;
+use std::{fmt, ops};
+use crate::error::{BexError, Result};
+use crate::mode::ErasedModes;
 
-/// A Trait for managing and analyzing a sequence (array/slice) of data one item at a time
+/// A Trait for managing and analyzing a sequence of data one item at a time
+///
+/// The sequence does not need to be fully in memory, see `SliceAnalyser` for analysers
+/// which can expose their entire contents.
+///
+/// Elements are looked at by reference. Methods returning them by value clone them, only `get`
+/// requires `Copy` and `get_cloned` covers elements which own data, like tokens holding strings.
+///
 /// # Type Parameters
 /// * `T` - Any type that is Sized (has a constant size in memory) and can be compared for equality
-pub trait Analyser<T: Sized + PartialEq + Copy> {
-    /// Get the entire sequence being analyzed
-    ///
-    /// # Returns
-    /// Array slice of the sequence being analyzed
-    fn contents(&self) -> &[T];
-
+pub trait Analyser<T: Sized + PartialEq> {
     /// Get the current position of cursor within the sequence
     ///
     /// # Returns
     /// Cursor position as usize
     fn pos(&self) -> usize;
 
-    /// Consumes the analyser, returning the sequence being analyzed
+    /// Sets the cursor to a given position
+    ///
+    /// Implementations must accept every position up to and including the length of the sequence
+    /// (the 'end of file' position) and reject anything past it with `BexError::CursorOutOfBounds`.
+    ///
+    /// # Arguments
+    /// * `position` - The index in sequence, where cursor will be placed
     ///
     /// # Returns
-    /// The sequence being analyzed as an owned vector
-    fn drain(self) -> Vec<T>;
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError`
+    fn set_pos(&mut self, position: usize) -> Result<()>;
 
-    /// Sets the cursor to a given position
+    /// Looks at the current element in the sequence without moving the cursor.
+    ///
+    /// # Returns
+    /// `Result<&T>` - Ok with a reference to the current element in the sequence, otherwise an Err with the `BexError` if the cursor is beyond the sequence bounds ('end of file' condition).
+    fn peek(&self) -> Result<&T>;
+
+    /// Move the cursor forward by a number of positions
     ///
     /// # Arguments
-    /// * `position` - The index in sequence, where cursor will be placed
+    /// * `count` - Number of positions to move by
     ///
     /// # Returns
-    /// `std::io::Result<()>` - Ok if operation successful, otherwise an Err with the `std::io::Error`
-    fn set_pos(&mut self, position: usize) -> io::Result<()>;
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError` if the cursor would pass the end of the sequence
+    fn advance(&mut self, count: usize) -> Result<()> {
+        self.set_pos(self.pos().saturating_add(count))
+    }
 
-    /// Move the cursor one position back
+    /// Move the cursor back by a number of positions
+    ///
+    /// # Arguments
+    /// * `count` - Number of positions to move by
     ///
     /// # Returns
-    /// `std::io::Result<()>` - Ok if operation successful, otherwise an Err with the `std::io::Error`
-    fn step_back(&mut self) -> io::Result<()>  { self.set_pos(self.pos() - 1) }
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError` if the cursor would move before the start of the sequence
+    fn retreat(&mut self, count: usize) -> Result<()> {
+        let position = self.pos().checked_sub(count)
+            .ok_or(BexError::CursorUnderflow { pos: self.pos(), count })?;
+        self.set_pos(position)
+    }
 
-    /// Move the cursor one position forward
+    /// Move the cursor one position back
     ///
     /// # Returns
-    /// `std::io::Result<()>` - Ok if operation successful, otherwise an Err with the `std::io::Error`
-    fn step_forward(&mut self) -> io::Result<()> { self.set_pos(self.pos() + 1) }
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError` if the cursor is at the start of the sequence
+    fn step_back(&mut self) -> Result<()>  { self.retreat(1) }
 
-    /// Get the length of sequence
+    /// Move the cursor one position forward
     ///
     /// # Returns
-    /// Length of the sequence as usize
-    fn len(&self) -> usize { self.contents().len() }
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError` if the cursor is at the end of the sequence
+    fn step_forward(&mut self) -> Result<()> { self.advance(1) }
 
     /// Check if end of sequence is reached by the cursor
     ///
+    /// Implementations which override this must return true exactly when `peek` fails.
+    ///
     /// # Returns
     /// Boolean that's true if end of sequence has been reached
-    fn is_end(&self) -> bool { self.pos() >= self.len() }
+    fn is_end(&self) -> bool { self.peek().is_err() }
 
     /// Resets the cursor to first position (at index 0)
     ///
     /// # Returns
-    /// `std::io::Result<()>` - Ok if operation successful, otherwise an Err with the `std::io::Error`
-    fn reset(&mut self) -> io::Result<()> { self.set_pos(0) }
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError`
+    fn reset(&mut self) -> Result<()> { self.set_pos(0) }
 
     /// Compares the current position's element with the target, moves cursor forward if they match
     ///
@@ -67,8 +94,8 @@ pub trait Analyser<T: Sized + PartialEq + Copy> {
     /// `target` - Target element to compare with the current element in sequence
     ///
     /// # Returns
-    /// `std::io::Result<bool>` - Ok if operation successful, containing true if element matched target and optionally moved forward, otherwise an Err with the `std::io::Error`
-    fn take(&mut self, target: &T) -> io::Result<bool> {
+    /// `Result<bool>` - Ok if operation successful, containing true if element matched target and optionally moved forward, otherwise an Err with the `BexError`
+    fn take(&mut self, target: &T) -> Result<bool> {
         let result = self.peek()? == target;
         if result { self.step_forward()?; }
         Ok(result)
@@ -80,8 +107,8 @@ pub trait Analyser<T: Sized + PartialEq + Copy> {
     /// * `target` -  An ordered sequence of target elements to compare and consume from the sequence
     ///
     /// # Returns
-    /// `std::io::Result<bool>` - Ok(true) if all elements in the sequence match the targets and move the cursor forward, otherwise Ok(false).
-    fn take_multi(&mut self, target: &[&T]) -> io::Result<bool>  {
+    /// `Result<bool>` - Ok(true) if all elements in the sequence match the targets and move the cursor forward, otherwise Ok(false).
+    fn take_multi(&mut self, target: &[&T]) -> Result<bool>  {
         for &element in target {
             match self.take(element) {
                 Ok(val) => {
@@ -93,50 +120,178 @@ pub trait Analyser<T: Sized + PartialEq + Copy> {
         Ok(true)
     }
 
-    /// Looks at the current element in the sequence without moving the cursor.
+    /// Consumes the current element if it satisfies the predicate
+    ///
+    /// # Arguments
+    /// * `predicate` - Test applied to the current element
+    ///
+    /// # Returns
+    /// `Result<Option<T>>` - Ok with the consumed element or `None` if it did not match, otherwise an Err with the `BexError`
+    fn take_if<F: FnOnce(&T) -> bool>(&mut self, predicate: F) -> Result<Option<T>> where Self: Sized, T: Clone {
+        if !predicate(self.peek()?) { return Ok(None) }
+        self.get_cloned().map(Some)
+    }
+
+    /// Consumes elements for as long as they satisfy the predicate or until the end of the sequence
+    ///
+    /// # Arguments
+    /// * `predicate` - Test applied to each element
+    ///
+    /// # Returns
+    /// `Result<Vec<T>>` - Ok with the consumed elements, otherwise an Err with the `BexError`
+    fn take_while<F: FnMut(&T) -> bool>(&mut self, mut predicate: F) -> Result<Vec<T>> where Self: Sized, T: Clone {
+        let mut result = vec![];
+        while !self.is_end() && predicate(self.peek()?) {
+            result.push(self.get_cloned()?);
+        }
+        Ok(result)
+    }
+
+    /// Moves the cursor past elements for as long as they satisfy the predicate or until the end of the sequence
+    ///
+    /// # Arguments
+    /// * `predicate` - Test applied to each element
+    ///
+    /// # Returns
+    /// `Result<usize>` - Ok with the number of skipped elements, otherwise an Err with the `BexError`
+    fn skip_while<F: FnMut(&T) -> bool>(&mut self, mut predicate: F) -> Result<usize> where Self: Sized {
+        let start = self.pos();
+        while !self.is_end() && predicate(self.peek()?) {
+            self.step_forward()?;
+        }
+        Ok(self.pos() - start)
+    }
+
+    /// Moves the cursor to the next element which equals any of the targets
+    ///
+    /// # Arguments
+    /// * `targets` - The elements to stop at
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok with the cursor on the matching element, otherwise an Err with the `BexError` ('end of file' if none was found)
+    fn seek_until_any(&mut self, targets: &[T]) -> Result<()> {
+        while !targets.contains(self.peek()?) {
+            self.step_forward()?;
+        }
+        Ok(())
+    }
+
+    /// Checks if the elements starting at the cursor match the target sequence, without moving the cursor
+    ///
+    /// # Arguments
+    /// * `target` - The sequence to compare against
+    ///
+    /// # Returns
+    /// `Result<bool>` - Ok(true) if the whole sequence matched, Ok(false) if it did not or the end was reached
+    fn peek_seq(&mut self, target: &[T]) -> Result<bool> {
+        let start = self.pos();
+        let mut matched = true;
+        for element in target {
+            if self.is_end() || !self.take(element)? {
+                matched = false;
+                break
+            }
+        }
+        self.set_pos(start)?;
+        Ok(matched)
+    }
+
+    /// Consumes elements up to a multi-element terminator such as `*/`, leaving the cursor at its start
+    ///
+    /// # Arguments
+    /// * `terminator` - The sequence to stop at
+    ///
+    /// # Returns
+    /// `Result<Vec<T>>` - Ok with the elements before the terminator, otherwise an Err with the `BexError` ('end of file' if it was not found)
+    fn get_until_seq(&mut self, terminator: &[T]) -> Result<Vec<T>> where T: Clone {
+        let mut result = vec![];
+        while !self.peek_seq(terminator)? {
+            result.push(self.get_cloned()?);
+        }
+        Ok(result)
+    }
+
+    /// Saves the current cursor position so it can be restored later
+    ///
+    /// # Returns
+    /// A `Checkpoint` to pass to `rollback`
+    fn checkpoint(&self) -> Checkpoint { Checkpoint { pos: self.pos(), modes: None } }
+
+    /// Moves the cursor back to a saved position
+    ///
+    /// # Arguments
+    /// * `checkpoint` - The position saved by `checkpoint`
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok if operation successful, otherwise an Err with the `BexError`
+    fn rollback(&mut self, checkpoint: Checkpoint) -> Result<()> { self.set_pos(checkpoint.pos) }
+
+    /// Saves the current cursor position in a guard which restores it when dropped
+    ///
+    /// The guard dereferences to the analyser, call `CheckpointGuard::commit` to keep the position
+    /// reached through it. Use `checkpoint` to store the position without borrowing the analyser.
     ///
     /// # Returns
-    /// `std::io::Result<&T>` - Ok with a reference to the current element in the sequence, otherwise an Err with the `std::io::Error` if the cursor is beyond the sequence bounds ('end of file' condition).
-    fn peek(&self) -> io::Result<&T> {
-        self.contents()
-            .get(self.pos())
-            .ok_or(
-                io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "End of file was reached unexpectedly."
-                )
-            )
+    /// A `CheckpointGuard` borrowing the analyser
+    fn guard(&mut self) -> CheckpointGuard<'_, T, Self> where Self: Sized {
+        let checkpoint = self.checkpoint();
+        CheckpointGuard { analyser: self, checkpoint: Some(checkpoint), element: PhantomData }
     }
 
-    fn get_until_as_range(&mut self, target: T) -> io::Result<ops::Range<usize>> {
+    /// Runs a speculative operation, restoring the cursor if it fails
+    ///
+    /// # Arguments
+    /// * `f` - The operation, failing when it returns `Err` or `None`
+    ///
+    /// # Returns
+    /// The result of `f`, or an error describing why the cursor could not be restored
+    fn try_with<R: Speculative, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R where Self: Sized {
+        let checkpoint = self.checkpoint();
+        let result = f(self);
+        if !result.is_failure() { return result }
+        match self.rollback(checkpoint) {
+            Ok(()) => result,
+            Err(e) => result.rollback_failed(e)
+        }
+    }
+
+    fn get_until_as_range(&mut self, target: T) -> Result<ops::Range<usize>> {
         let start = self.pos();
         self.seek_until(target)?;
         Ok(start..self.pos())
     }
 
-    fn get_until(&mut self, target: T) -> io::Result<Vec<T>> {
-        let range = self.get_until_as_range(target)?;
-        Ok(self.contents()[range].to_owned())
+    fn get_until(&mut self, target: T) -> Result<Vec<T>> where T: Clone {
+        let mut result = vec![];
+        while *self.peek()? != target {
+            result.push(self.get_cloned()?);
+        }
+        Ok(result)
     }
 
-    fn space_until(&mut self, target: T) -> io::Result<usize> {
+    fn space_until(&mut self, target: T) -> Result<usize> {
         let start = self.pos();
         self.seek_until(target)?;
-        return Ok(self.pos() - start);
+        Ok(self.pos() - start)
     }
 
-    fn seek_until(&mut self, target: T) -> io::Result<()> {
-        Ok(loop {
-            let current = self.peek()?;
-            if *current == target {
-                break;
-            }
-        })
+    /// Moves the cursor to the next element which equals the target
+    ///
+    /// # Arguments
+    /// * `target` - The element to stop at
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok with the cursor on the matching element, otherwise an Err with the `BexError` ('end of file' if it was not found)
+    fn seek_until(&mut self, target: T) -> Result<()> {
+        while *self.peek()? != target {
+            self.step_forward()?;
+        }
+        Ok(())
     }
 
-    fn get_not(&mut self, target: T) -> io::Result<T> {
+    fn get_not(&mut self, target: T) -> Result<T> where T: Clone {
         loop {
-            let mut found = self.get()?;
+            let found = self.get_cloned()?;
             if found == target { continue }
             return Ok(found);
         }
@@ -145,19 +300,163 @@ pub trait Analyser<T: Sized + PartialEq + Copy> {
     /// Gets the current element and then moves the cursor forward by one position.
     ///
     /// # Returns
-    /// `std::io::Result<T>` - Ok with a copy of the current element in the sequence, otherwise an Err with the `std::io::Error` if the cursor is beyond the sequence bounds ('end of file' condition).
-    fn get(&mut self) -> io::Result<T> {
+    /// `Result<T>` - Ok with a copy of the current element in the sequence, otherwise an Err with the `BexError` if the cursor is beyond the sequence bounds ('end of file' condition).
+    fn get(&mut self) -> Result<T> where T: Copy {
         let current = *self.peek()?;
         self.step_forward()?;
-        return Ok(current)
+        Ok(current)
+    }
+
+    /// Gets a clone of the current element and then moves the cursor forward by one position, for elements which are not `Copy`
+    ///
+    /// # Returns
+    /// `Result<T>` - Ok with a clone of the current element in the sequence, otherwise an Err with the `BexError` if the cursor is beyond the sequence bounds ('end of file' condition).
+    fn get_cloned(&mut self) -> Result<T> where T: Clone {
+        let current = self.peek()?.clone();
+        self.step_forward()?;
+        Ok(current)
+    }
+
+}
+
+/// A saved cursor position, created by `Analyser::checkpoint`
+///
+/// Checkpoints of a `Lexer` also hold a copy of its mode stack, so rolling back restores the
+/// modes pushed or popped since.
+#[must_use = "a checkpoint does nothing unless it is rolled back to"]
+pub struct Checkpoint {
+    pos:   usize,
+    modes: Option<Box<dyn ErasedModes>>
+}
+
+impl Checkpoint {
+    /// Get the saved cursor position
+    ///
+    /// # Returns
+    /// Cursor position as usize
+    pub fn pos(&self) -> usize { self.pos }
+
+    pub(crate) fn with_modes(pos: usize, modes: Option<Box<dyn ErasedModes>>) -> Self { Self { pos, modes } }
+
+    pub(crate) fn into_modes(self) -> Option<Box<dyn ErasedModes>> { self.modes }
+}
+
+impl Clone for Checkpoint {
+    fn clone(&self) -> Self { Self { pos: self.pos, modes: self.modes.as_ref().map(|it| it.clone_boxed()) } }
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpoint").field("pos", &self.pos).field("modes", &self.modes.is_some()).finish()
     }
+}
 
+/// Restores the cursor of an analyser when dropped unless committed, created by `Analyser::guard`
+///
+/// A failed rollback cannot be reported from `drop`, so streams which may have discarded the
+/// saved position should use `checkpoint` and `rollback` instead.
+///
+/// # Type Parameters
+/// * `T` - The type of the elements being analyzed.
+/// * `A` - The guarded analyser.
+#[must_use = "dropping the guard immediately restores the cursor"]
+pub struct CheckpointGuard<'a, T: Sized + PartialEq, A: Analyser<T>> {
+    analyser:   &'a mut A,
+    checkpoint: Option<Checkpoint>,
+    element:    PhantomData<fn(&T)>
 }
 
-impl<T: Sized + PartialEq + Copy> ops::Index<ops::Range<usize>> for dyn Analyser<T> {
+impl<T: Sized + PartialEq, A: Analyser<T>> CheckpointGuard<'_, T, A> {
+    /// Keeps the cursor where it is instead of restoring it
+    pub fn commit(mut self) { self.checkpoint = None; }
+}
+
+impl<T: Sized + PartialEq, A: Analyser<T>> ops::Deref for CheckpointGuard<'_, T, A> {
+    type Target = A;
+
+    fn deref(&self) -> &A { self.analyser }
+}
+
+impl<T: Sized + PartialEq, A: Analyser<T>> ops::DerefMut for CheckpointGuard<'_, T, A> {
+    fn deref_mut(&mut self) -> &mut A { self.analyser }
+}
+
+impl<T: Sized + PartialEq, A: Analyser<T>> Drop for CheckpointGuard<'_, T, A> {
+    fn drop(&mut self) {
+        if let Some(checkpoint) = self.checkpoint.take() {
+            let _ = self.analyser.rollback(checkpoint);
+        }
+    }
+}
+
+/// The outcome of an operation run through `Analyser::try_with`
+pub trait Speculative {
+    /// Check if the operation failed and the cursor should be restored
+    fn is_failure(&self) -> bool;
+
+    /// Converts a failed outcome into one reporting that the cursor could not be restored
+    ///
+    /// # Arguments
+    /// * `error` - The error returned while restoring the cursor
+    fn rollback_failed(self, error: BexError) -> Self;
+}
+
+impl<T, E: From<BexError>> Speculative for Result<T, E> {
+    fn is_failure(&self) -> bool { self.is_err() }
+
+    fn rollback_failed(self, error: BexError) -> Self { Err(error.into()) }
+}
+
+impl<T> Speculative for Option<T> {
+    fn is_failure(&self) -> bool { self.is_none() }
+
+    fn rollback_failed(self, _error: BexError) -> Self { None }
+}
+
+/// An `Analyser` whose entire sequence (array/slice) is held in memory
+///
+/// # Type Parameters
+/// * `T` - Any type that is Sized (has a constant size in memory) and can be compared for equality
+pub trait SliceAnalyser<T: Sized + PartialEq>: Analyser<T> {
+    /// Get the entire sequence being analyzed
+    ///
+    /// # Returns
+    /// Array slice of the sequence being analyzed
+    fn contents(&self) -> &[T];
+
+    /// Consumes the analyser, returning the sequence being analyzed
+    ///
+    /// # Returns
+    /// The sequence being analyzed as an owned vector
+    fn drain(self) -> Vec<T>;
+
+    /// Get the length of sequence
+    ///
+    /// # Returns
+    /// Length of the sequence as usize
+    fn len(&self) -> usize { self.contents().len() }
+
+    /// Check if the sequence is empty
+    ///
+    /// # Returns
+    /// Boolean that's true if the sequence has no elements
+    fn is_empty(&self) -> bool { self.contents().is_empty() }
+}
+
+/// Looks up the element under the cursor of a `SliceAnalyser`, for use in `Analyser::peek` implementations.
+///
+/// # Returns
+/// `Result<&T>` - Ok with a reference to the current element, otherwise an `UnexpectedEof` error.
+pub(crate) fn peek_contents<T: Sized + PartialEq>(contents: &[T], pos: usize) -> Result<&T> {
+    contents
+        .get(pos)
+        .ok_or(BexError::UnexpectedEof { pos })
+}
+
+impl<T: Sized + PartialEq> ops::Index<ops::Range<usize>> for dyn SliceAnalyser<T> {
     type Output = [T];
 
     fn index(&self, range: ops::Range<usize>) -> &[T] {
         &self.contents()[range]
     }
-}
\ No newline at end of file
+}
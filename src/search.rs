@@ -0,0 +1,126 @@
+//! SIMD accelerated searches over bytes
+//!
+//! The generic `Analyser` methods such as `seek_until`, `get_until` and `get_until_seq` still read
+//! one element at a time when `T` is `u8`, they are not routed through `memchr`. Callers lexing
+//! bytes have to switch to the `ByteSearch` methods to get the faster searches.
+
+use memchr::{memchr, memchr2, memchr3, memmem};
+use crate::error::{BexError, Result};
+use crate::read::SliceAnalyser;
+
+/// Searches over bytes using SIMD accelerated `memchr`, instead of reading one element at a time
+///
+/// Like the `Analyser` methods they replace, the searches leave the cursor at the start of the match,
+/// or at the end of the sequence with an 'end of file' error if there is none. The `skip_until`
+/// methods stop at the end without an error, like `Analyser::skip_while`.
+pub trait ByteSearch: SliceAnalyser<u8> {
+    /// Moves the cursor to the next occurrence of a byte, see `Analyser::seek_until`
+    ///
+    /// # Arguments
+    /// * `target` - The byte to stop at
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok with the cursor on the byte, otherwise an Err with the `BexError` ('end of file' if it was not found)
+    fn seek_byte(&mut self, target: u8) -> Result<()> { seek(self, |rest| memchr(target, rest)) }
+
+    /// Moves the cursor to the next byte which equals any of the targets, see `Analyser::seek_until_any`
+    ///
+    /// Sets of up to three bytes are searched with SIMD, larger sets one byte at a time.
+    ///
+    /// # Arguments
+    /// * `targets` - The bytes to stop at
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok with the cursor on the matching byte, otherwise an Err with the `BexError` ('end of file' if none was found)
+    fn seek_any_byte(&mut self, targets: &[u8]) -> Result<()> { seek(self, |rest| find_any(targets, rest)) }
+
+    /// Moves the cursor to the start of the next occurrence of a byte sequence such as `*/`
+    ///
+    /// # Arguments
+    /// * `terminator` - The sequence to stop at
+    ///
+    /// # Returns
+    /// `Result<()>` - Ok with the cursor on the start of the sequence, otherwise an Err with the `BexError` ('end of file' if it was not found)
+    fn seek_bytes(&mut self, terminator: &[u8]) -> Result<()> { seek(self, |rest| memmem::find(rest, terminator)) }
+
+    /// Moves the cursor past every byte before the next occurrence of a byte or to the end of the
+    /// sequence, like `Analyser::skip_while` with a predicate comparing against `target`
+    ///
+    /// # Arguments
+    /// * `target` - The byte to stop at
+    ///
+    /// # Returns
+    /// `Result<usize>` - Ok with the number of skipped bytes, otherwise an Err with the `BexError`
+    fn skip_until_byte(&mut self, target: u8) -> Result<usize> { skip(self, |rest| memchr(target, rest)) }
+
+    /// Moves the cursor past every byte before the next byte which equals any of the targets or to
+    /// the end of the sequence, see `skip_until_byte`
+    ///
+    /// # Arguments
+    /// * `targets` - The bytes to stop at
+    ///
+    /// # Returns
+    /// `Result<usize>` - Ok with the number of skipped bytes, otherwise an Err with the `BexError`
+    fn skip_until_any_byte(&mut self, targets: &[u8]) -> Result<usize> { skip(self, |rest| find_any(targets, rest)) }
+
+    /// Consumes bytes up to the next occurrence of a byte, see `Analyser::get_until`
+    ///
+    /// # Arguments
+    /// * `target` - The byte to stop at
+    ///
+    /// # Returns
+    /// `Result<Vec<u8>>` - Ok with the bytes before the target, otherwise an Err with the `BexError` ('end of file' if it was not found)
+    fn get_until_byte(&mut self, target: u8) -> Result<Vec<u8>> {
+        let start = self.pos();
+        self.seek_byte(target)?;
+        Ok(self.contents()[start..self.pos()].to_vec())
+    }
+
+    /// Consumes bytes up to a multi-byte terminator, leaving the cursor at its start, see `Analyser::get_until_seq`
+    ///
+    /// # Arguments
+    /// * `terminator` - The sequence to stop at
+    ///
+    /// # Returns
+    /// `Result<Vec<u8>>` - Ok with the bytes before the terminator, otherwise an Err with the `BexError` ('end of file' if it was not found)
+    fn get_until_bytes(&mut self, terminator: &[u8]) -> Result<Vec<u8>> {
+        let start = self.pos();
+        self.seek_bytes(terminator)?;
+        Ok(self.contents()[start..self.pos()].to_vec())
+    }
+}
+
+impl<A: SliceAnalyser<u8> + ?Sized> ByteSearch for A {}
+
+/// Finds the first byte which equals any of the targets, with SIMD for sets of up to three bytes.
+fn find_any(targets: &[u8], rest: &[u8]) -> Option<usize> {
+    match *targets {
+        [] => None,
+        [first] => memchr(first, rest),
+        [first, second] => memchr2(first, second, rest),
+        [first, second, third] => memchr3(first, second, third, rest),
+        _ => rest.iter().position(|it| targets.contains(it))
+    }
+}
+
+/// Moves the cursor by the offset `find` returns for the rest of the contents, or to the end if it finds nothing.
+fn skip<A: SliceAnalyser<u8> + ?Sized>(analyser: &mut A, find: impl FnOnce(&[u8]) -> Option<usize>) -> Result<usize> {
+    let start = analyser.pos();
+    let rest = &analyser.contents()[start..];
+    let offset = find(rest).unwrap_or(rest.len());
+    analyser.set_pos(start + offset)?;
+    Ok(offset)
+}
+
+/// Moves the cursor by the offset `find` returns for the rest of the contents, or to the end if it finds nothing.
+fn seek<A: SliceAnalyser<u8> + ?Sized>(analyser: &mut A, find: impl FnOnce(&[u8]) -> Option<usize>) -> Result<()> {
+    let start = analyser.pos();
+    match find(&analyser.contents()[start..]) {
+        Some(offset) => analyser.set_pos(start + offset),
+        None => {
+            let end = analyser.contents().len();
+            analyser.set_pos(end)?;
+            Err(BexError::UnexpectedEof { pos: end })
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::{fmt, ops};
+use crate::error::Result;
+use crate::lexer::Lexer;
+use crate::location::{LineCol, LineIndex, LineSource};
+use crate::vfs::{normalize_path, FileSystem, IncludeResolver};
+
+/// Identifies a file loaded into a `SourceDb`
+///
+/// Ids are only meaningful for the database that handed them out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// Get the position of the file in its database
+    pub fn index(self) -> usize { self.0 as usize }
+}
+
+impl fmt::Display for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "#{}", self.0) }
+}
+
+/// A range of a specific file, so spans can cross include boundaries
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileSpan {
+    pub file:  FileId,
+    pub range: ops::Range<usize>
+}
+
+impl FileSpan {
+    pub fn new(file: FileId, range: ops::Range<usize>) -> Self { Self { file, range } }
+}
+
+/// A file owned by a `SourceDb`
+///
+/// # Type Parameters
+/// * `T` - The type of the elements the file consists of.
+#[derive(Debug)]
+pub struct SourceFile<T: Sized + PartialEq + Clone> {
+    id:       FileId,
+    name:     Arc<str>,
+    contents: Vec<T>,
+    lines:    OnceLock<Arc<LineIndex>>
+}
+
+impl<T: Sized + PartialEq + Clone> SourceFile<T> {
+    /// Get the id of the file
+    pub fn id(&self) -> FileId { self.id }
+
+    /// Get the name the file was loaded under
+    pub fn name(&self) -> &Arc<str> { &self.name }
+
+    /// Get the contents of the file
+    pub fn contents(&self) -> &[T] { &self.contents }
+
+    /// Creates a lexer borrowing the contents of the file, tagged with its id
+    pub fn lexer(&self) -> Lexer<'_, T> { Lexer::from_slice(&self.contents).with_file(self.id) }
+
+    /// Get a span of the file
+    ///
+    /// # Arguments
+    /// * `range` - The range of the file
+    pub fn span(&self, range: ops::Range<usize>) -> FileSpan { FileSpan::new(self.id, range) }
+}
+
+impl<T: LineSource> SourceFile<T> {
+    /// Get the line index of the file, built on first use and shared afterwards
+    pub fn line_index(&self) -> &Arc<LineIndex> {
+        self.lines.get_or_init(|| Arc::new(LineIndex::new(&self.contents)))
+    }
+
+    /// Converts an offset of the file into a line and column
+    ///
+    /// # Arguments
+    /// * `offset` - Offset within the file
+    pub fn line_col(&self, offset: usize) -> Option<LineCol> { self.line_index().line_col(offset) }
+}
+
+/// Owns every file loaded during a build and assigns each a `FileId`
+///
+/// Files are keyed by their normalized path, compared case-insensitively like in Arma, so a
+/// header included from many files is only read and stored once.
+///
+/// # Type Parameters
+/// * `T` - The type of the elements the files consist of.
+#[derive(Debug)]
+pub struct SourceDb<T: Sized + PartialEq + Clone = u8> {
+    files: Vec<Arc<SourceFile<T>>>,
+    paths: HashMap<String, FileId>
+}
+
+impl<T: Sized + PartialEq + Clone> Default for SourceDb<T> {
+    fn default() -> Self { Self { files: vec![], paths: HashMap::new() } }
+}
+
+fn path_key(name: &str) -> String { normalize_path(name).to_lowercase() }
+
+impl<T: Sized + PartialEq + Clone> SourceDb<T> {
+    pub fn new() -> Self { Self::default() }
+
+    /// Get the number of files in the database
+    pub fn len(&self) -> usize { self.files.len() }
+
+    /// Check if no file has been loaded
+    pub fn is_empty(&self) -> bool { self.files.is_empty() }
+
+    /// Adds a file, replacing the name and contents of a file already loaded under the same path
+    ///
+    /// # Arguments
+    /// * `name` - Path of the file
+    /// * `contents` - Contents of the file
+    ///
+    /// # Returns
+    /// The id of the file, which stays the same when its contents are replaced
+    pub fn add<C: Into<Vec<T>>>(&mut self, name: &str, contents: C) -> FileId {
+        let key = path_key(name);
+        let id = match self.paths.get(&key) {
+            Some(id) => *id,
+            None => FileId(self.files.len() as u32)
+        };
+        let file = Arc::new(SourceFile { id, name: Arc::from(name), contents: contents.into(), lines: OnceLock::new() });
+        match self.files.get_mut(id.index()) {
+            Some(existing) => *existing = file,
+            None => {
+                self.files.push(file);
+                self.paths.insert(key, id);
+            }
+        }
+        id
+    }
+
+    /// Get the id of a loaded file
+    ///
+    /// # Arguments
+    /// * `name` - Path of the file
+    pub fn file_id(&self, name: &str) -> Option<FileId> { self.paths.get(&path_key(name)).copied() }
+
+    /// Get a loaded file
+    ///
+    /// # Arguments
+    /// * `id` - Id of the file
+    ///
+    /// # Returns
+    /// `Some` with the file, `None` if the id was handed out by another database
+    pub fn get(&self, id: FileId) -> Option<&Arc<SourceFile<T>>> { self.files.get(id.index()) }
+
+    /// Creates a lexer borrowing the contents of a file, tagged with its id
+    ///
+    /// # Arguments
+    /// * `id` - Id of the file
+    pub fn lexer(&self, id: FileId) -> Option<Lexer<'_, T>> { self.get(id).map(|it| it.lexer()) }
+
+    /// Iterates over every loaded file in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<SourceFile<T>>> { self.files.iter() }
+}
+
+impl SourceDb<u8> {
+    /// Loads a file from a file system unless it is already loaded
+    ///
+    /// # Arguments
+    /// * `file_system` - The file system to read the file from
+    /// * `path` - Path of the file
+    ///
+    /// # Returns
+    /// `Result<FileId>` - Ok with the id of the file, otherwise an Err with the `BexError` of the read
+    pub fn open<F: FileSystem + ?Sized>(&mut self, file_system: &F, path: &str) -> Result<FileId> {
+        if let Some(id) = self.file_id(path) { return Ok(id) }
+        let contents = file_system.read(&normalize_path(path))?;
+        Ok(self.add(path, contents))
+    }
+
+    /// Resolves an include relative to a loaded file and loads it unless it is already loaded
+    ///
+    /// # Arguments
+    /// * `resolver` - Resolves and reads the included file
+    /// * `from` - Id of the including file
+    /// * `include` - Path written in the directive
+    ///
+    /// # Returns
+    /// `Result<FileId>` - Ok with the id of the included file, otherwise an Err with the `BexError` of the resolver
+    pub fn include<R: IncludeResolver + ?Sized>(&mut self, resolver: &R, from: FileId, include: &str) -> Result<FileId> {
+        let from = self.get(from).map_or_else(|| Arc::from("/"), |it| it.name.clone());
+        let path = resolver.resolve(&from, include)?;
+        if let Some(id) = self.file_id(&path) { return Ok(id) }
+        let contents = resolver.read_resolved(&path)?;
+        Ok(self.add(&path, contents))
+    }
+}
+
+impl<T: Sized + PartialEq + Clone> ops::Index<FileId> for SourceDb<T> {
+    type Output = Arc<SourceFile<T>>;
+
+    fn index(&self, id: FileId) -> &Self::Output { &self.files[id.index()] }
+}
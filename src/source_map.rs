@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::{fmt, ops};
+use crate::diagnostic::{Diagnostic, Label, ToDiagnostic};
+use crate::error::BexError;
+use crate::lexer::Lexer;
+use crate::location::{LineCol, LineIndex, LineSource};
+use crate::source_db::{FileId, FileSpan, SourceFile};
+
+/// A macro invocation which produced part of the preprocessed output
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Expansion {
+    pub name:      String,
+    pub call_site: FileSpan
+}
+
+/// Where a range of preprocessed output was read from
+///
+/// For text copied verbatim `span` is the exact original range. For text produced by macros
+/// `span` is the outermost invocation and `expansions` lists every macro involved, outermost first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    pub span:       FileSpan,
+    pub expansions: Vec<Expansion>
+}
+
+impl Origin {
+    /// Creates an origin for text copied verbatim from a file
+    pub fn verbatim(span: FileSpan) -> Self {
+        Self { span, expansions: vec![] }
+    }
+
+    /// Check if the text was produced by a macro
+    pub fn is_expansion(&self) -> bool { !self.expansions.is_empty() }
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    output: ops::Range<usize>,
+    origin: Origin
+}
+
+/// Maps ranges of preprocessed output back to the files and ranges they were produced from
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    segments: Vec<Segment>,
+    files:    HashMap<FileId, (Arc<str>, Arc<LineIndex>)>
+}
+
+impl SourceMap {
+    pub fn new() -> Self { Self::default() }
+
+    /// Registers an original file so resolved offsets can be converted into lines and columns
+    ///
+    /// Registering a file again, such as after its contents were replaced in the `SourceDb`,
+    /// replaces its name and line index.
+    ///
+    /// # Arguments
+    /// * `file` - The file, as loaded into a `SourceDb`
+    pub fn add_file<T: LineSource>(&mut self, file: &SourceFile<T>) {
+        self.files.insert(file.id(), (file.name().clone(), file.line_index().clone()));
+    }
+
+    /// Get the name of a registered file
+    ///
+    /// # Arguments
+    /// * `file` - Id of the file
+    pub fn file_name(&self, file: FileId) -> Option<&Arc<str>> { self.files.get(&file).map(|(name, _)| name) }
+
+    /// Converts an offset of a registered file into a line and column
+    ///
+    /// # Arguments
+    /// * `span` - The span whose start is converted
+    pub fn line_col(&self, span: &FileSpan) -> Option<LineCol> {
+        self.files.get(&span.file).and_then(|(_, lines)| lines.line_col(span.range.start))
+    }
+
+    /// Records where a range of output came from, ranges must be pushed in increasing order
+    ///
+    /// Adjacent verbatim ranges of the same file are merged into a single segment.
+    ///
+    /// # Arguments
+    /// * `output` - The range of the preprocessed output
+    /// * `origin` - Where the output was produced from
+    pub fn push(&mut self, output: ops::Range<usize>, origin: Origin) {
+        if output.is_empty() { return }
+        if let Some(last) = self.segments.last_mut() {
+            let verbatim = !origin.is_expansion() && !last.origin.is_expansion();
+            if verbatim && last.output.end == output.start && last.origin.span.file == origin.span.file
+                && last.origin.span.range.end == origin.span.range.start {
+                last.output.end = output.end;
+                last.origin.span.range.end = origin.span.range.end;
+                return
+            }
+        }
+        self.segments.push(Segment { output, origin });
+    }
+
+    /// Resolves an offset of the output to where it was produced from
+    ///
+    /// # Arguments
+    /// * `offset` - Offset within the preprocessed output
+    ///
+    /// # Returns
+    /// `Some` with the exact original offset for verbatim text or the invocation for expanded text,
+    /// `None` if no segment covers the offset. The end of the output resolves to an empty range at
+    /// the end of the last segment's origin, so errors at the end of the input are located.
+    pub fn resolve(&self, offset: usize) -> Option<Origin> {
+        if let Some(last) = self.segments.last().filter(|it| it.output.end == offset) {
+            let mut origin = last.origin.clone();
+            origin.span.range = origin.span.range.end..origin.span.range.end;
+            return Some(origin)
+        }
+        let index = self.segments.partition_point(|it| it.output.end <= offset);
+        let segment = self.segments.get(index).filter(|it| it.output.start <= offset)?;
+        let mut origin = segment.origin.clone();
+        if !origin.is_expansion() {
+            let start = origin.span.range.start + (offset - segment.output.start);
+            origin.span.range = start..start + 1;
+        }
+        Some(origin)
+    }
+
+    /// Resolves an offset of the output to a line and column of the original file
+    ///
+    /// # Arguments
+    /// * `offset` - Offset within the preprocessed output
+    ///
+    /// # Returns
+    /// `Some` with the origin and the location of its start, `None` if the offset is not covered
+    pub fn resolve_location(&self, offset: usize) -> Option<(Origin, Option<LineCol>)> {
+        let origin = self.resolve(offset)?;
+        let location = self.line_col(&origin.span);
+        Some((origin, location))
+    }
+}
+
+/// The output of a `PreProcess` implementation together with its `SourceMap`
+///
+/// # Type Parameters
+/// * `T` - The type of the elements being preprocessed.
+#[derive(Debug)]
+pub struct Preprocessed<T: Sized + PartialEq + Clone> {
+    pub output:     Vec<T>,
+    pub source_map: SourceMap
+}
+
+impl<T: Sized + PartialEq + Clone> Preprocessed<T> {
+    /// Creates a lexer borrowing the preprocessed output
+    pub fn lexer(&self) -> Lexer<'_, T> { Lexer::from_slice(&self.output) }
+}
+
+/// Errors which know the range of the sequence they were raised at
+pub trait HasSpan {
+    /// Get the range of the sequence the error was raised at
+    ///
+    /// # Returns
+    /// `Some` with the range, `None` for errors without a position
+    fn span(&self) -> Option<ops::Range<usize>>;
+}
+
+impl HasSpan for BexError {
+    fn span(&self) -> Option<ops::Range<usize>> { BexError::span(self) }
+}
+
+/// An error raised on preprocessed output, resolved back to the original source
+#[derive(Debug)]
+pub struct Located<E> {
+    pub error:      E,
+    pub origin:     Option<Origin>,
+    pub file:       Option<Arc<str>>,
+    pub location:   Option<LineCol>,
+    /// Name and location of the call site of each of `origin.expansions`
+    pub call_sites: Vec<(Option<Arc<str>>, Option<LineCol>)>
+}
+
+impl<E: HasSpan> Located<E> {
+    /// Resolves the span of an error through a source map
+    ///
+    /// # Arguments
+    /// * `error` - The error raised on the preprocessed output
+    /// * `source_map` - The source map of that output
+    pub fn resolve(error: E, source_map: &SourceMap) -> Self {
+        match error.span().and_then(|span| source_map.resolve_location(span.start)) {
+            Some((origin, location)) => {
+                let file = source_map.file_name(origin.span.file).cloned();
+                let call_sites = origin.expansions.iter()
+                    .map(|it| (source_map.file_name(it.call_site.file).cloned(), source_map.line_col(&it.call_site)))
+                    .collect();
+                Self { error, origin: Some(origin), file, location, call_sites }
+            }
+            None => Self { error, origin: None, file: None, location: None, call_sites: vec![] }
+        }
+    }
+}
+
+fn write_location(f: &mut fmt::Formatter<'_>, file: &Option<Arc<str>>, span: &FileSpan, location: &Option<LineCol>) -> fmt::Result {
+    match file {
+        Some(name) => write!(f, "{name}")?,
+        None => write!(f, "{}", span.file)?
+    }
+    match location {
+        Some(location) => write!(f, ":{location}"),
+        None => write!(f, "@{}", span.range.start)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Located<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            write_location(f, &self.file, &origin.span, &self.location)?;
+            write!(f, ": ")?;
+        }
+        write!(f, "{:?}", self.error)?;
+        if let Some(origin) = &self.origin {
+            for (expansion, (file, location)) in origin.expansions.iter().zip(&self.call_sites) {
+                write!(f, "\n  in expansion of {} at ", expansion.name)?;
+                write_location(f, file, &expansion.call_site, location)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Display> ToDiagnostic for Located<E> {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(self.error.to_string());
+        if let Some(origin) = &self.origin {
+            diagnostic = diagnostic.with_label(Label::primary(origin.span.clone(), ""));
+            for expansion in &origin.expansions {
+                let message = format!("in this expansion of {}", expansion.name);
+                diagnostic = match expansion.call_site == origin.span {
+                    true => diagnostic.with_note(message),
+                    false => diagnostic.with_label(Label::secondary(expansion.call_site.clone(), message))
+                };
+            }
+        }
+        diagnostic
+    }
+}
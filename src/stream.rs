@@ -0,0 +1,165 @@
+use std::io;
+use crate::error::{BexError, Result};
+use crate::read::Analyser;
+
+/// Number of bytes requested from the reader each time the window is refilled
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of bytes behind the cursor that are retained for backtracking
+pub const DEFAULT_LOOKBEHIND: usize = 4 * 1024;
+
+/// Analyser over an `io::Read` which only keeps a sliding window of the stream in memory
+///
+/// The window always holds the byte under the cursor (unless the stream is exhausted) and at
+/// least `lookbehind` bytes before it, so the cursor can be moved back by that many bytes.
+/// Positions are absolute offsets into the stream.
+///
+/// # Type Parameters
+/// * `R` - The reader the bytes are pulled from.
+pub struct ReaderAnalyser<R: io::Read> {
+    reader:       R,
+    window:       Vec<u8>,
+    window_start: usize,
+    cursor:       usize,
+    chunk_size:   usize,
+    lookbehind:   usize,
+    exhausted:    bool
+}
+
+impl<R: io::Read> ReaderAnalyser<R> {
+    /// Creates an analyser with the default chunk size and lookbehind
+    ///
+    /// # Arguments
+    /// * `reader` - The reader to analyze
+    ///
+    /// # Returns
+    /// `Result<Self>` - Err if the first chunk could not be read
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_capacity(reader, DEFAULT_CHUNK_SIZE, DEFAULT_LOOKBEHIND)
+    }
+
+    /// Creates an analyser with a custom chunk size and lookbehind
+    ///
+    /// # Arguments
+    /// * `reader` - The reader to analyze
+    /// * `chunk_size` - Number of bytes read from `reader` on every refill, at least one
+    /// * `lookbehind` - Number of bytes behind the cursor that can be backtracked over
+    ///
+    /// # Returns
+    /// `Result<Self>` - Err if the first chunk could not be read
+    pub fn with_capacity(reader: R, chunk_size: usize, lookbehind: usize) -> Result<Self> {
+        let mut analyser = Self {
+            reader,
+            window: Vec::with_capacity(chunk_size.max(1) + lookbehind),
+            window_start: 0,
+            cursor: 0,
+            chunk_size: chunk_size.max(1),
+            lookbehind,
+            exhausted: false,
+        };
+        analyser.fill_to(0)?;
+        Ok(analyser)
+    }
+
+    /// Get the part of the stream currently held in memory
+    ///
+    /// # Returns
+    /// The absolute offset of the first retained byte and the retained bytes
+    pub fn window(&self) -> (usize, &[u8]) { (self.window_start, &self.window) }
+
+    /// Get the earliest position the cursor can currently be moved back to
+    ///
+    /// # Returns
+    /// Absolute offset of the first retained byte
+    pub fn window_start(&self) -> usize { self.window_start }
+
+    /// Consumes the analyser, returning the underlying reader
+    ///
+    /// # Returns
+    /// The reader, positioned after the last byte pulled into the window
+    pub fn into_inner(self) -> R { self.reader }
+
+    fn window_end(&self) -> usize { self.window_start + self.window.len() }
+
+    /// Reads chunks until the byte at `position` is in the window or the stream is exhausted.
+    ///
+    /// Bytes further than `lookbehind` behind `position` are dropped before each chunk is read, so
+    /// skipping far ahead does not buffer everything in between.
+    fn fill_to(&mut self, position: usize) -> Result<()> {
+        while !self.exhausted && position >= self.window_end() {
+            self.discard_behind(position);
+            let old_len = self.window.len();
+            self.window.resize(old_len + self.chunk_size, 0);
+            let read = loop {
+                match self.reader.read(&mut self.window[old_len..]) {
+                    Ok(read) => break read,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        self.window.truncate(old_len);
+                        return Err(e.into())
+                    }
+                }
+            };
+            self.window.truncate(old_len + read);
+            self.exhausted = read == 0;
+        }
+        Ok(())
+    }
+
+    /// Drops bytes which are further than `lookbehind` behind `position` from the window.
+    fn discard_behind(&mut self, position: usize) {
+        let keep_from = position.saturating_sub(self.lookbehind).max(self.window_start);
+        let discard = (keep_from - self.window_start).min(self.window.len());
+        if discard > 0 {
+            self.window.drain(..discard);
+            self.window_start += discard;
+        }
+    }
+}
+
+impl<R: io::Read> Analyser<u8> for ReaderAnalyser<R> {
+    /// Get the current position of cursor within the stream
+    ///
+    /// # Returns
+    /// Absolute cursor position as usize
+    fn pos(&self) -> usize { self.cursor }
+
+    /// Sets the cursor to a given position, reading from the stream as needed
+    ///
+    /// # Parameters
+    /// * `position: usize` - The absolute offset in the stream, where cursor will be placed
+    ///
+    /// # Returns
+    /// `Result<()>` - Err if `position` is before the retained window, past the end of the stream, or the read failed.
+    /// The bytes read while moving forward are dropped even if it fails, so the cursor is then left on
+    /// the first retained byte if its own byte was dropped, which is the end of the stream if `position` was past it.
+    fn set_pos(&mut self, position: usize) -> Result<()> {
+        if position < self.window_start {
+            return Err(BexError::RewindPastWindow { pos: position, window_start: self.window_start })
+        }
+        let filled = self.fill_to(position);
+        self.cursor = self.cursor.max(self.window_start);
+        filled?;
+        if position > self.window_end() {
+            return Err(BexError::CursorOutOfBounds { pos: position, len: self.window_end() })
+        }
+        self.cursor = position;
+        Ok(())
+    }
+
+    /// Looks at the current byte in the stream without moving the cursor.
+    ///
+    /// # Returns
+    /// `Result<&u8>` - Ok with a reference to the current byte, otherwise an `UnexpectedEof` error
+    fn peek(&self) -> Result<&u8> {
+        self.window
+            .get(self.cursor - self.window_start)
+            .ok_or(BexError::UnexpectedEof { pos: self.cursor })
+    }
+
+    /// Check if end of stream is reached by the cursor
+    ///
+    /// # Returns
+    /// Boolean that's true if the stream is exhausted and the cursor is past its last byte
+    fn is_end(&self) -> bool { self.exhausted && self.cursor >= self.window_end() }
+}
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+use crate::lexer::{Lexer, Token};
+use crate::read::{Analyser, Checkpoint};
+
+/// Lazily generates tokens from a `Lexer`, with lookahead over any number of tokens
+///
+/// The stream ends at the end of the lexer's contents, or after the first error has been yielded.
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the lexer's source.
+/// * `TokenType` - The type of token being generated.
+/// * `T` - The type of the elements being lexed.
+pub struct TokenStream<'a, TokenType: Token<'a, T>, T: Sized + PartialEq + Clone> {
+    lexer:     Lexer<'a, T>,
+    lookahead: VecDeque<(Checkpoint, Result<TokenType, TokenType::Error>)>,
+    finished:  bool
+}
+
+impl<'a, TokenType: Token<'a, T>, T: Sized + PartialEq + Clone> TokenStream<'a, TokenType, T> {
+    /// Creates a token stream starting at the lexer's current position
+    ///
+    /// # Arguments
+    /// * `lexer` - Lexer from which the tokens should be generated.
+    pub fn new(lexer: Lexer<'a, T>) -> Self {
+        Self { lexer, lookahead: VecDeque::new(), finished: false }
+    }
+
+    /// Looks at the next token without consuming it
+    ///
+    /// # Returns
+    /// `None` if the stream has ended, otherwise the result of generating the next token
+    pub fn peek(&mut self) -> Option<&Result<TokenType, TokenType::Error>> { self.peek_nth(0) }
+
+    /// Looks `n` tokens ahead without consuming any of them
+    ///
+    /// # Arguments
+    /// * `n` - Number of tokens to skip over, `0` is the next token
+    ///
+    /// # Returns
+    /// `None` if the stream ends before that token, otherwise the result of generating it
+    pub fn peek_nth(&mut self, n: usize) -> Option<&Result<TokenType, TokenType::Error>> {
+        while self.lookahead.len() <= n && !self.finished {
+            self.advance();
+        }
+        self.lookahead.get(n).map(|(_, result)| result)
+    }
+
+    /// Get the lexer the tokens are generated from
+    ///
+    /// # Returns
+    /// The lexer, positioned after any tokens buffered by `peek_nth`
+    pub fn lexer(&self) -> &Lexer<'a, T> { &self.lexer }
+
+    /// Consumes the stream, returning the lexer
+    ///
+    /// # Returns
+    /// The lexer, positioned at the start of the first token which has not been consumed and with
+    /// the mode stack it had before that token
+    pub fn into_lexer(mut self) -> Lexer<'a, T> {
+        if let Some((start, _)) = self.lookahead.pop_front() {
+            self.lexer.rollback(start).expect("buffered tokens start within the lexer");
+        }
+        self.lexer
+    }
+
+    fn advance(&mut self) {
+        let start = self.lexer.checkpoint();
+        let result = match TokenType::skip_ignored(&mut self.lexer) {
+            Ok(()) if self.lexer.is_end() => {
+                self.finished = true;
+                return
+            }
+            Ok(()) => TokenType::next_token(&mut self.lexer),
+            Err(error) => Err(error)
+        };
+        self.finished = result.is_err();
+        self.lookahead.push_back((start, result));
+    }
+}
+
+impl<'a, TokenType: Token<'a, T>, T: Sized + PartialEq + Clone> Iterator for TokenStream<'a, TokenType, T> {
+    type Item = Result<TokenType, TokenType::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.lookahead.is_empty() && !self.finished {
+            self.advance();
+        }
+        self.lookahead.pop_front().map(|(_, result)| result)
+    }
+}
+
+impl<'a, T: Sized + PartialEq + Clone> Lexer<'a, T> {
+    /// Consumes the lexer, returning a stream which generates tokens on demand
+    ///
+    /// # Returns
+    /// A `TokenStream` starting at the current position
+    pub fn token_stream<TokenType: Token<'a, T>>(self) -> TokenStream<'a, TokenType, T> {
+        TokenStream::new(self)
+    }
+}
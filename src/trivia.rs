@@ -0,0 +1,187 @@
+use std::borrow::Cow;
+use std::ops;
+use memchr::{memchr, memmem};
+use crate::error::Result;
+use crate::lexer::{Lexer, Token};
+use crate::read::{Analyser, SliceAnalyser};
+
+/// The kinds of input which carry no meaning for a parser but must be kept to reproduce the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriviaKind {
+    /// Spaces and tabs
+    Whitespace,
+    /// A single line ending, `\n` or `\r\n`
+    Newline,
+    /// A `//` comment, excluding the line ending
+    LineComment,
+    /// A `/* */` comment, which may span lines or be left unterminated at the end of the input
+    BlockComment
+}
+
+/// A piece of trivia and the range of the source it covers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: ops::Range<usize>
+}
+
+/// Tokens which know how to skip the trivia between them, see `Lexer::tokenize_lossless`
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the lexer's source.
+/// * `T` - The type of the elements being lexed.
+pub trait TriviaToken<'a, T: Sized + PartialEq + Clone>: Token<'a, T> {
+    /// Reads a single piece of trivia at the cursor
+    ///
+    /// # Arguments
+    /// * `lexer` - Lexer from which the trivia should be read.
+    ///
+    /// # Returns
+    /// `Some` with the kind of trivia after moving the cursor past it, `None` without moving the
+    /// cursor if the next element starts a token
+    fn next_trivia(lexer: &mut Lexer<'a, T>) -> Result<Option<TriviaKind>, Self::Error>;
+}
+
+/// Reads whitespace, line endings and `//` or `/* */` comments, for `TriviaToken::next_trivia`
+///
+/// # Arguments
+/// * `lexer` - Lexer from which the trivia should be read.
+pub fn c_style_trivia(lexer: &mut Lexer<'_, u8>) -> Result<Option<TriviaKind>> {
+    let rest = &lexer.contents()[lexer.pos()..];
+    let (kind, length) = match rest {
+        [b'\n', ..] | [b'\r', b'\n', ..] => (TriviaKind::Newline, if rest[0] == b'\r' { 2 } else { 1 }),
+        [b' ' | b'\t', ..] => (TriviaKind::Whitespace, rest.iter().position(|it| *it != b' ' && *it != b'\t').unwrap_or(rest.len())),
+        [b'/', b'/', ..] => (TriviaKind::LineComment, line_comment_length(rest)),
+        [b'/', b'*', ..] => (
+            TriviaKind::BlockComment,
+            memmem::find(&rest[2..], b"*/").map_or(rest.len(), |it| it + 4)
+        ),
+        _ => return Ok(None)
+    };
+    lexer.advance(length)?;
+    Ok(Some(kind))
+}
+
+/// Length of a `//` comment, stopping before a `\n` or `\r\n` line ending.
+fn line_comment_length(rest: &[u8]) -> usize {
+    match memchr(b'\n', rest) {
+        Some(end) if end > 0 && rest[end - 1] == b'\r' => end - 1,
+        Some(end) => end,
+        None => rest.len()
+    }
+}
+
+/// A token together with the trivia around it
+///
+/// Trailing trivia is everything after the token up to, but excluding, the next line ending.
+/// Everything else before a token is its leading trivia.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessToken<TokenType> {
+    pub leading:  Vec<Trivia>,
+    pub token:    TokenType,
+    pub span:     ops::Range<usize>,
+    pub trailing: Vec<Trivia>
+}
+
+impl<TokenType> LosslessToken<TokenType> {
+    /// Get the range covered by the token and its trivia
+    pub fn full_span(&self) -> ops::Range<usize> {
+        let start = self.leading.first().map_or(self.span.start, |it| it.span.start);
+        let end = self.trailing.last().map_or(self.span.end, |it| it.span.end);
+        start..end
+    }
+}
+
+/// Tokens with all of the trivia between them, created by `Lexer::tokenize_lossless`
+///
+/// # Type Parameters
+/// * `'a` - Lifetime of the lexer's source.
+/// * `TokenType` - The type of the tokens.
+/// * `T` - The type of the elements that were lexed.
+#[derive(Debug)]
+pub struct LosslessTokens<'a, TokenType, T: Sized + PartialEq + Clone> {
+    source:     Cow<'a, [T]>,
+    start:      usize,
+    tokens:     Vec<LosslessToken<TokenType>>,
+    end_trivia: Vec<Trivia>
+}
+
+impl<TokenType, T: Sized + PartialEq + Clone> LosslessTokens<'_, TokenType, T> {
+    /// Get the tokens in source order
+    pub fn tokens(&self) -> &[LosslessToken<TokenType>] { &self.tokens }
+
+    /// Get the trivia after the last token's trailing trivia
+    pub fn end_trivia(&self) -> &[Trivia] { &self.end_trivia }
+
+    /// Get the source elements of a token or trivia span
+    ///
+    /// # Arguments
+    /// * `span` - A span of a token or trivia
+    pub fn text(&self, span: &ops::Range<usize>) -> &[T] { &self.source[span.clone()] }
+
+    /// Reproduces the source the tokens were read from
+    ///
+    /// # Returns
+    /// The elements of every trivia and token in order, equal to the lexed part of the input
+    pub fn to_source(&self) -> Vec<T> {
+        let mut source = Vec::with_capacity(self.source.len() - self.start);
+        for token in &self.tokens {
+            for trivia in &token.leading { source.extend_from_slice(self.text(&trivia.span)); }
+            source.extend_from_slice(self.text(&token.span));
+            for trivia in &token.trailing { source.extend_from_slice(self.text(&trivia.span)); }
+        }
+        for trivia in &self.end_trivia { source.extend_from_slice(self.text(&trivia.span)); }
+        source
+    }
+}
+
+impl<'a, T: Sized + PartialEq + Clone> Lexer<'a, T> {
+    /// Tokenizes the remaining contents, keeping every piece of trivia so the input can be reproduced
+    ///
+    /// # Returns
+    /// The tokens and their trivia, or the first error returned by `TriviaToken`
+    pub fn tokenize_lossless<
+        TokenType: TriviaToken<'a, T>
+    >(mut self) -> Result<LosslessTokens<'a, TokenType, T>, TokenType::Error> {
+        let start = self.pos();
+        let mut tokens: Vec<LosslessToken<TokenType>> = vec![];
+        let mut pending = vec![];
+        loop {
+            while let Some(trivia) = read_trivia::<TokenType, T>(&mut self)? {
+                pending.push(trivia);
+            }
+            if self.is_end() { break }
+            let token_start = self.pos();
+            let token = TokenType::next_token(&mut self)?;
+            let span = token_start..self.pos();
+            let mut trailing = vec![];
+            while let Some(trivia) = read_trivia::<TokenType, T>(&mut self)? {
+                if trivia.kind == TriviaKind::Newline {
+                    self.set_pos(trivia.span.start)?;
+                    break
+                }
+                trailing.push(trivia);
+            }
+            tokens.push(LosslessToken { leading: std::mem::take(&mut pending), token, span, trailing });
+        }
+        let source = match self.source_slice(0..self.len()) {
+            Some(source) => Cow::Borrowed(source),
+            None => Cow::Owned(self.drain())
+        };
+        Ok(LosslessTokens { source, start, tokens, end_trivia: pending })
+    }
+}
+
+/// Reads a piece of trivia, treating trivia which does not move the cursor as the start of a token.
+fn read_trivia<'a, TokenType: TriviaToken<'a, T>, T: Sized + PartialEq + Clone>(
+    lexer: &mut Lexer<'a, T>
+) -> Result<Option<Trivia>, TokenType::Error> {
+    let start = lexer.pos();
+    match TokenType::next_trivia(lexer)? {
+        Some(kind) if lexer.pos() > start => Ok(Some(Trivia { kind, span: start..lexer.pos() })),
+        _ => {
+            lexer.set_pos(start)?;
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::str;
+use crate::error::{BexError, Result};
+use crate::read::SliceAnalyser;
+
+/// Reads characters from UTF-8 encoded bytes, such as a `Lexer<u8>`
+///
+/// The cursor stays a byte offset, so spans can be used with the bytes and with the `Analyser`
+/// methods. Invalid or truncated sequences are reported as `BexError::InvalidUtf8` with the range
+/// of the offending bytes.
+pub trait Utf8Analyser: SliceAnalyser<u8> {
+    /// Looks at the character starting at the cursor without moving the cursor
+    ///
+    /// # Returns
+    /// `Result<char>` - Ok with the character, otherwise an Err with the `BexError` ('end of file' at the end of the sequence, `InvalidUtf8` if the bytes are not a character)
+    fn peek_char(&self) -> Result<char> { decode(self.contents(), self.pos()).map(|(character, _)| character) }
+
+    /// Gets the character starting at the cursor and then moves the cursor past its bytes
+    ///
+    /// # Returns
+    /// `Result<char>` - Ok with the character, otherwise an Err with the `BexError` ('end of file' at the end of the sequence, `InvalidUtf8` if the bytes are not a character)
+    fn get_char(&mut self) -> Result<char> {
+        let (character, length) = decode(self.contents(), self.pos())?;
+        self.advance(length)?;
+        Ok(character)
+    }
+
+    /// Compares the character at the cursor with the target, moves the cursor past it if they match
+    ///
+    /// # Arguments
+    /// * `target` - Target character to compare with the current character
+    ///
+    /// # Returns
+    /// `Result<bool>` - Ok with true if the character matched and the cursor moved past it, otherwise an Err with the `BexError`
+    fn take_char(&mut self, target: char) -> Result<bool> {
+        let (character, length) = decode(self.contents(), self.pos())?;
+        if character == target { self.advance(length)?; }
+        Ok(character == target)
+    }
+
+    /// Moves the cursor past characters for as long as they satisfy the predicate or until the end of the sequence
+    ///
+    /// # Arguments
+    /// * `predicate` - Test applied to each character
+    ///
+    /// # Returns
+    /// `Result<usize>` - Ok with the number of skipped bytes, otherwise an Err with the `BexError` if an invalid sequence was reached
+    fn skip_chars_while<F: FnMut(char) -> bool>(&mut self, mut predicate: F) -> Result<usize> where Self: Sized {
+        let start = self.pos();
+        while !self.is_end() {
+            let (character, length) = decode(self.contents(), self.pos())?;
+            if !predicate(character) { break }
+            self.advance(length)?;
+        }
+        Ok(self.pos() - start)
+    }
+}
+
+impl<A: SliceAnalyser<u8> + ?Sized> Utf8Analyser for A {}
+
+/// Decodes the character starting at `pos`, returning it with its length in bytes.
+fn decode(contents: &[u8], pos: usize) -> Result<(char, usize)> {
+    let rest = contents.get(pos..).filter(|it| !it.is_empty()).ok_or(BexError::UnexpectedEof { pos })?;
+    let bytes = &rest[..rest.len().min(4)];
+    let valid = match str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) if e.valid_up_to() > 0 => str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+        Err(e) => {
+            let length = e.error_len().unwrap_or(bytes.len());
+            return Err(BexError::InvalidUtf8 { span: pos..pos + length })
+        }
+    };
+    let character = valid.chars().next().ok_or(BexError::UnexpectedEof { pos })?;
+    Ok((character, character.len_utf8()))
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::{fs, io, path};
+use crate::error::{BexError, Result};
+
+/// Normalizes a Bohemia-style path into an absolute virtual path
+///
+/// Backslashes become forward slashes, `.` and `..` segments are resolved and a leading `/` is
+/// added, so `x\cba\addons\..\main.hpp` becomes `/x/cba/main.hpp`. Case is preserved.
+///
+/// # Arguments
+/// * `path` - The path to normalize
+pub fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split(['/', '\\']) {
+        match segment {
+            "" | "." => {}
+            ".." => { segments.pop(); }
+            _ => segments.push(segment)
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// Get the directory part of a normalized virtual path
+fn parent_of(path: &str) -> &str {
+    path.rfind('/').map_or("", |it| &path[..it])
+}
+
+fn not_found(path: &str) -> BexError {
+    io::Error::new(io::ErrorKind::NotFound, format!("{path} does not exist.")).into()
+}
+
+/// A source of files addressed by normalized virtual paths, see `normalize_path`
+pub trait FileSystem {
+    /// Reads the contents of a file
+    ///
+    /// # Arguments
+    /// * `path` - Normalized virtual path of the file
+    ///
+    /// # Returns
+    /// `Result<Vec<u8>>` - Ok with the contents, otherwise an Err with the `BexError` (`NotFound` if there is no such file)
+    fn read(&self, path: &str) -> Result<Vec<u8>>;
+
+    /// Check if a file exists, without reading its contents
+    ///
+    /// # Arguments
+    /// * `path` - Normalized virtual path of the file
+    fn exists(&self, path: &str) -> bool;
+}
+
+/// Resolves the path written in an `#include` directive to a file
+pub trait IncludeResolver {
+    /// Resolves an include relative to the file containing it
+    ///
+    /// # Arguments
+    /// * `from` - Path of the including file
+    /// * `include` - Path written in the directive, absolute if it starts with `\` or `/`
+    ///
+    /// # Returns
+    /// `Result<String>` - Ok with the normalized path of the included file, otherwise an Err with the `BexError`
+    fn resolve(&self, from: &str, include: &str) -> Result<String>;
+
+    /// Reads a file returned by `resolve`
+    ///
+    /// # Arguments
+    /// * `path` - Normalized path of the file
+    ///
+    /// # Returns
+    /// `Result<Vec<u8>>` - Ok with the contents, otherwise an Err with the `BexError`
+    fn read_resolved(&self, path: &str) -> Result<Vec<u8>>;
+
+    /// Resolves an include and reads the included file
+    ///
+    /// # Arguments
+    /// * `from` - Path of the including file
+    /// * `include` - Path written in the directive
+    ///
+    /// # Returns
+    /// `Result<(String, Vec<u8>)>` - Ok with the normalized path and contents, otherwise an Err with the `BexError`
+    fn load(&self, from: &str, include: &str) -> Result<(String, Vec<u8>)> {
+        let path = self.resolve(from, include)?;
+        let contents = self.read_resolved(&path)?;
+        Ok((path, contents))
+    }
+}
+
+impl<F: FileSystem + ?Sized> IncludeResolver for F {
+    fn resolve(&self, from: &str, include: &str) -> Result<String> {
+        let path = match include.starts_with(['/', '\\']) {
+            true => normalize_path(include),
+            false => normalize_path(&format!("{}/{include}", parent_of(&normalize_path(from))))
+        };
+        match self.exists(&path) {
+            true => Ok(path),
+            false => Err(not_found(&path))
+        }
+    }
+
+    fn read_resolved(&self, path: &str) -> Result<Vec<u8>> { self.read(path) }
+}
+
+/// A file system held entirely in memory, paths are matched case-insensitively like in Arma
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+    files: HashMap<String, Vec<u8>>
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds or replaces a file
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file, normalized before it is stored
+    /// * `contents` - Contents of the file
+    pub fn insert<C: Into<Vec<u8>>>(&mut self, path: &str, contents: C) {
+        self.files.insert(normalize_path(path).to_lowercase(), contents.into());
+    }
+
+    /// Adds or replaces a file, returning the file system for chaining
+    ///
+    /// # Arguments
+    /// * `path` - Path of the file, normalized before it is stored
+    /// * `contents` - Contents of the file
+    pub fn with_file<C: Into<Vec<u8>>>(mut self, path: &str, contents: C) -> Self {
+        self.insert(path, contents);
+        self
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.files.get(&normalize_path(path).to_lowercase()).cloned().ok_or_else(|| not_found(path))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(&normalize_path(path).to_lowercase())
+    }
+}
+
+/// A file system backed by directories on disk
+///
+/// Virtual paths are first matched against prefix mappings, such as `\x\cba` mapped to a checkout
+/// of CBA, and then looked up in each search path in order, like a mounted P-drive. Prefixes and
+/// files are matched case-insensitively like in Arma, also on hosts with case-sensitive file systems.
+#[derive(Debug, Clone, Default)]
+pub struct DiskFileSystem {
+    mappings:     Vec<(String, path::PathBuf)>,
+    search_paths: Vec<path::PathBuf>
+}
+
+impl DiskFileSystem {
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds a directory which virtual paths are looked up in
+    ///
+    /// # Arguments
+    /// * `directory` - Directory on disk acting as the root of the virtual file system
+    pub fn with_search_path<P: Into<path::PathBuf>>(mut self, directory: P) -> Self {
+        self.search_paths.push(directory.into());
+        self
+    }
+
+    /// Maps a virtual path prefix to a directory on disk
+    ///
+    /// # Arguments
+    /// * `prefix` - Virtual path prefix, such as `\x\cba`
+    /// * `directory` - Directory on disk the prefix refers to
+    pub fn with_prefix<P: Into<path::PathBuf>>(mut self, prefix: &str, directory: P) -> Self {
+        self.mappings.push((normalize_path(prefix), directory.into()));
+        self
+    }
+
+    /// Get every location on disk a virtual path may refer to, in lookup order
+    ///
+    /// The locations keep the case of `path`, see `find` for the file they refer to.
+    ///
+    /// # Arguments
+    /// * `path` - Virtual path of the file
+    pub fn candidates(&self, path: &str) -> Vec<path::PathBuf> {
+        let path = normalize_path(path);
+        let mapped = self.mappings.iter().filter_map(|(prefix, directory)| {
+            let rest = path.get(prefix.len()..).filter(|_| path[..prefix.len()].to_lowercase() == prefix.to_lowercase())?;
+            (rest.is_empty() || rest.starts_with('/') || prefix == "/").then(|| directory.join(rest.trim_start_matches('/')))
+        });
+        let searched = self.search_paths.iter().map(|directory| directory.join(path.trim_start_matches('/')));
+        mapped.chain(searched).collect()
+    }
+
+    /// Get the file on disk a virtual path refers to
+    ///
+    /// # Arguments
+    /// * `path` - Virtual path of the file
+    ///
+    /// # Returns
+    /// `Some` with the first candidate which exists when compared case-insensitively, otherwise `None`
+    pub fn find(&self, path: &str) -> Option<path::PathBuf> {
+        self.candidates(path).iter().find_map(|it| find_ignoring_case(it))
+    }
+}
+
+/// Finds a file on disk, comparing each path component which does not exist as written case-insensitively.
+fn find_ignoring_case(candidate: &path::Path) -> Option<path::PathBuf> {
+    if candidate.is_file() { return Some(candidate.to_path_buf()) }
+    let mut found = path::PathBuf::new();
+    for component in candidate.components() {
+        let exact = found.join(component);
+        found = match component {
+            path::Component::Normal(name) if !exact.exists() => {
+                let name = name.to_str()?.to_lowercase();
+                let directory = match found.as_os_str().is_empty() {
+                    true => path::Path::new("."),
+                    false => found.as_path()
+                };
+                fs::read_dir(directory).ok()?
+                    .filter_map(|it| it.ok())
+                    .find(|it| it.file_name().to_str().is_some_and(|it| it.to_lowercase() == name))?
+                    .path()
+            }
+            _ => exact
+        };
+    }
+    found.is_file().then_some(found)
+}
+
+impl FileSystem for DiskFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        let file = self.find(path).ok_or_else(|| not_found(path))?;
+        Ok(fs::read(file)?)
+    }
+
+    fn exists(&self, path: &str) -> bool { self.find(path).is_some() }
+}
+
+/// Combines several file systems, each file is read from the first layer which has it
+#[derive(Default)]
+pub struct LayeredFileSystem {
+    layers: Vec<Box<dyn FileSystem>>
+}
+
+impl LayeredFileSystem {
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds a layer below the existing ones
+    ///
+    /// # Arguments
+    /// * `layer` - The file system to fall back to
+    pub fn with_layer<F: FileSystem + 'static>(mut self, layer: F) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+}
+
+impl FileSystem for LayeredFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        for layer in &self.layers {
+            match layer.read(path) {
+                Err(BexError::Io(e)) if e.kind() == io::ErrorKind::NotFound => continue,
+                result => return result
+            }
+        }
+        Err(not_found(path))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.layers.iter().any(|it| it.exists(path))
+    }
+}
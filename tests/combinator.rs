@@ -0,0 +1,116 @@
+use bex::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tok {
+    Class,
+    Ident,
+    Number,
+    LBrace,
+    RBrace,
+    Equals,
+    Semicolon,
+    Comma
+}
+
+#[derive(Debug, PartialEq)]
+enum Entry {
+    Class(Vec<Entry>),
+    Property(Vec<Tok>)
+}
+
+fn digit(input: &mut Lexer<u8>) -> ParseResult<u8> {
+    map(satisfy("a digit", u8::is_ascii_digit), |it| it - b'0')(input)
+}
+
+fn entry(input: &mut Lexer<Tok>) -> ParseResult<Entry> {
+    alt((
+        map(seq((token(Tok::Class), cut(token(Tok::Ident)), delimited(token(Tok::LBrace), many(entry), token(Tok::RBrace)), token(Tok::Semicolon))),
+            |(_, _, entries, _)| Entry::Class(entries)),
+        map(seq((token(Tok::Ident), token(Tok::Equals), sep_by(alt((token(Tok::Number), token(Tok::Ident))), token(Tok::Comma)), token(Tok::Semicolon))),
+            |(_, _, values, _)| Entry::Property(values))
+    ))(input)
+}
+
+#[test]
+fn combinators_parse_bytes() {
+    let mut lexer = Lexer::from_slice(b"{1,2,3}x");
+    let values = delimited(token(b'{'), sep_by(digit, token(b',')), token(b'}'))(&mut lexer).unwrap();
+    assert_eq!(values, [1, 2, 3]);
+    assert_eq!(lexer.pos(), 7);
+
+    let mut lexer = Lexer::from_slice(b"1,2,");
+    assert_eq!(sep_by(digit, token(b',')).parse(&mut lexer).unwrap(), [1, 2]);
+    assert_eq!(lexer.pos(), 3);
+
+    let mut lexer = Lexer::from_slice(b"class");
+    assert_eq!(opt(tag(b"clash")).parse(&mut lexer).unwrap(), None);
+    assert_eq!(lexer.pos(), 0);
+    assert_eq!(tag(b"class")(&mut lexer).unwrap(), b"class");
+}
+
+#[test]
+fn combinators_parse_tokens() {
+    use Tok::*;
+    let mut lexer = Lexer::from_vec(vec![Class, Ident, LBrace, Ident, Equals, Number, Comma, Ident, Semicolon, RBrace, Semicolon]);
+    assert_eq!(entry(&mut lexer).unwrap(), Entry::Class(vec![Entry::Property(vec![Number, Ident])]));
+    assert!(lexer.is_end());
+}
+
+#[test]
+fn failures_list_everything_expected() {
+    let mut lexer = Lexer::from_vec(vec![Tok::Equals]);
+    let error = entry(&mut lexer).unwrap_err();
+    assert_eq!((error.pos, error.expected.as_slice()), (0, &["Class".to_string(), "Ident".to_string()][..]));
+    assert_eq!(error.to_string(), "Expected Class or Ident but found Equals at offset 0.");
+
+    let mut lexer = Lexer::from_slice(b"x");
+    let error = label("a number", digit)(&mut lexer).unwrap_err();
+    assert_eq!(error.to_string(), "Expected a number but found 120 at offset 0.");
+}
+
+#[test]
+fn cut_stops_backtracking() {
+    use Tok::*;
+    let mut lexer = Lexer::from_vec(vec![Class, Number]);
+    let error = alt((entry, map(token(Class), |_| Entry::Property(vec![]))))(&mut lexer).unwrap_err();
+    assert!(error.cut);
+    assert_eq!((error.pos, error.expected.as_slice()), (1, &["Ident".to_string()][..]));
+
+    let mut lexer = Lexer::from_vec(vec![Class, Number]);
+    assert!(many(entry).parse(&mut lexer).is_err());
+    let error: BexError = entry(&mut Lexer::from_vec(vec![Class, Number])).unwrap_err().into();
+    assert!(matches!(error, BexError::Unexpected { ref expected, .. } if expected == "Ident"));
+}
+
+#[test]
+fn failures_after_optional_parts_list_what_they_expected() {
+    use Tok::*;
+    let mut lexer = Lexer::from_vec(vec![Ident, Ident, Number]);
+    let error = seq((many(token(Ident)), token(Semicolon)))(&mut lexer).unwrap_err();
+    assert_eq!((error.pos, error.expected.as_slice()), (2, &["Ident".to_string(), "Semicolon".to_string()][..]));
+
+    let mut lexer = Lexer::from_vec(vec![Number]);
+    let error = seq((opt(token(Comma)), many(token(Equals)), token(Semicolon)))(&mut lexer).unwrap_err();
+    assert_eq!(error.expected, ["Comma", "Equals", "Semicolon"]);
+
+    let mut lexer = Lexer::from_vec(vec![LBrace, Ident, Equals, Number, Semicolon, Number]);
+    let error = delimited(token(LBrace), many(entry), token(RBrace))(&mut lexer).unwrap_err();
+    assert_eq!((error.pos, error.expected.as_slice()), (5, &["Class".to_string(), "Ident".to_string(), "RBrace".to_string()][..]));
+}
+
+#[test]
+fn backtracking_restores_the_modes() {
+    let push_then_fail = |input: &mut Lexer<u8>| -> ParseResult<u8> {
+        input.modes_mut::<u8>().push(1);
+        input.step_forward()?;
+        Err(ParseError::expected(input.pos(), "nothing", None))
+    };
+    let mut lexer = Lexer::from_slice(b"ab");
+    lexer.modes_mut::<u8>();
+    assert_eq!(alt((push_then_fail, token(b'a')))(&mut lexer).unwrap(), b'a');
+    assert_eq!(lexer.modes::<u8>().map(ModeStack::depth), Some(0));
+
+    assert_eq!(opt(push_then_fail).parse(&mut lexer).unwrap(), None);
+    assert_eq!(many(push_then_fail).parse(&mut lexer).unwrap(), []);
+    assert_eq!((lexer.pos(), lexer.modes::<u8>().map(ModeStack::depth)), (1, Some(0)));
+}
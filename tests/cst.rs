@@ -0,0 +1,133 @@
+use bex::*;
+
+const FILE: SyntaxKind = SyntaxKind(0);
+const CLASS: SyntaxKind = SyntaxKind(1);
+const ASSIGN: SyntaxKind = SyntaxKind(2);
+const WORD: SyntaxKind = SyntaxKind(10);
+const PUNCT: SyntaxKind = SyntaxKind(11);
+const SPACE: SyntaxKind = SyntaxKind(12);
+
+/// Builds `class A { x = 1; y = 1; };` with every assignment in its own node.
+fn tree() -> SyntaxNode {
+    let mut builder = GreenNodeBuilder::new();
+    builder.start_node(FILE);
+    builder.start_node(CLASS);
+    for (kind, text) in [(WORD, "class"), (SPACE, " "), (WORD, "A"), (SPACE, " "), (PUNCT, "{"), (SPACE, " ")] {
+        builder.token(kind, text.as_bytes());
+    }
+    for name in ["x", "y"] {
+        let checkpoint = builder.checkpoint();
+        builder.token(WORD, name.as_bytes());
+        builder.start_node_at(checkpoint, ASSIGN);
+        for (kind, text) in [(SPACE, " "), (PUNCT, "="), (SPACE, " "), (WORD, "1"), (PUNCT, ";")] {
+            builder.token(kind, text.as_bytes());
+        }
+        builder.finish_node();
+        builder.token(SPACE, b" ");
+    }
+    builder.token(PUNCT, b"}");
+    builder.finish_node();
+    builder.token(PUNCT, b";");
+    builder.finish_node();
+    SyntaxNode::new_root(builder.finish())
+}
+
+#[test]
+fn trees_are_lossless_and_know_their_offsets() {
+    let root = tree();
+    assert_eq!(root.text(), b"class A { x = 1; y = 1; };");
+    let assignments: Vec<_> = root.descendants().filter(|it| it.kind() == ASSIGN).collect();
+    assert_eq!(assignments.iter().map(|it| it.text_range()).collect::<Vec<_>>(), [10..16, 17..23]);
+    assert_eq!(assignments[1].to_string(), "y = 1;");
+    assert_eq!(assignments[1].ancestors().map(|it| it.kind()).collect::<Vec<_>>(), [ASSIGN, CLASS, FILE]);
+    assert_eq!(assignments[0].parent().unwrap().kind(), CLASS);
+}
+
+#[test]
+fn tokens_can_be_queried_and_walked() {
+    let root = tree();
+    let token = root.token_at_offset(14).unwrap();
+    assert_eq!((token.text(), token.text_range()), (&b"1"[..], 14..15));
+    assert_eq!(token.parent().kind(), ASSIGN);
+
+    let semicolon = token.next_token().unwrap();
+    assert_eq!(semicolon.text(), b";");
+    let space = semicolon.next_token().unwrap();
+    assert_eq!((space.text(), space.parent().kind()), (&b" "[..], CLASS));
+    assert_eq!(space.next_token().unwrap().text(), b"y");
+    assert_eq!(root.last_token().unwrap().text_range(), 25..26);
+
+    let class = root.child_of_kind(CLASS).unwrap();
+    assert_eq!(class.token_of_kind(PUNCT).unwrap().text(), b"{");
+    assert_eq!(root.covering_element(11..14).kind(), ASSIGN);
+    assert_eq!(root.covering_element(0..2).kind(), WORD);
+}
+
+#[test]
+fn identical_tokens_and_small_nodes_are_interned() {
+    let root = tree();
+    let ones: Vec<_> = root.descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .filter(|it| it.text() == b"1")
+        .collect();
+    assert_eq!(ones.len(), 2);
+    assert_ne!(ones[0], ones[1]);
+    assert!(ones[0].green().ptr_eq(ones[1].green()));
+
+    let mut cache = NodeCache::new();
+    let a = cache.token(WORD, b"a");
+    let b = cache.token(WORD, b"a");
+    let first = cache.node(ASSIGN, vec![GreenElement::Token(a)]);
+    let second = cache.node(ASSIGN, vec![GreenElement::Token(b)]);
+    assert!(first.ptr_eq(&second));
+}
+
+/// Builds `(a)` with empty `ASSIGN` nodes before the first and after the last token.
+fn tree_with_empty_nodes() -> SyntaxNode {
+    let mut builder = GreenNodeBuilder::new();
+    builder.start_node(FILE);
+    builder.start_node(ASSIGN);
+    builder.finish_node();
+    builder.token(PUNCT, b"(");
+    builder.start_node(CLASS);
+    builder.token(WORD, b"a");
+    builder.start_node(ASSIGN);
+    builder.finish_node();
+    builder.finish_node();
+    builder.token(PUNCT, b")");
+    builder.start_node(CLASS);
+    builder.start_node(ASSIGN);
+    builder.finish_node();
+    builder.finish_node();
+    builder.finish_node();
+    SyntaxNode::new_root(builder.finish())
+}
+
+#[test]
+fn first_and_last_tokens_skip_empty_nodes() {
+    let root = tree_with_empty_nodes();
+    assert_eq!(root.first_token().unwrap().text_range(), 0..1);
+    assert_eq!(root.last_token().unwrap().text_range(), 2..3);
+    let class = root.child_of_kind(CLASS).unwrap();
+    assert_eq!(class.last_token().unwrap().text(), b"a");
+    assert_eq!(root.children().last().unwrap().first_token(), None);
+    assert_eq!(root.children().last().unwrap().last_token(), None);
+}
+
+#[test]
+fn siblings_know_their_offsets() {
+    let root = tree();
+    let class = root.child_of_kind(CLASS).unwrap();
+    let elements: Vec<_> = class.children_with_tokens().collect();
+    for pair in elements.windows(2) {
+        assert_eq!(pair[0].next_sibling_or_token().as_ref(), Some(&pair[1]));
+        assert_eq!(pair[1].prev_sibling_or_token().as_ref(), Some(&pair[0]));
+        assert_eq!(pair[0].text_range().end, pair[1].text_range().start);
+    }
+    assert_eq!(elements[0].prev_sibling_or_token(), None);
+    assert_eq!(elements.last().unwrap().next_sibling_or_token(), None);
+
+    let assignment = class.child_of_kind(ASSIGN).unwrap();
+    assert_eq!(assignment.prev_sibling_or_token().unwrap().text_range(), 9..10);
+    assert_eq!(assignment.next_sibling_or_token().unwrap().text_range(), 16..17);
+}
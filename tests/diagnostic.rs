@@ -0,0 +1,56 @@
+use bex::*;
+
+fn sources() -> (SourceDb, FileId, FileId) {
+    let mut sources: SourceDb = SourceDb::new();
+    let config = sources.add("config.cpp", "class A {\n\tx = G(foo);\n};\n");
+    let macros = sources.add("script_macros.hpp", "#define G(a) a\n");
+    (sources, config, macros)
+}
+
+#[test]
+fn diagnostics_render_source_excerpts() {
+    let (sources, config, macros) = sources();
+    let diagnostic = Diagnostic::error("Unknown macro.")
+        .with_code("unknown-macro")
+        .with_label(Label::primary(FileSpan::new(config, 15..21), "used here"))
+        .with_label(Label::secondary(FileSpan::new(macros, 8..9), "defined here"))
+        .with_help("include script_macros.hpp");
+    assert_eq!(diagnostic.render(&sources, false), "\
+error[unknown-macro]: Unknown macro.
+ --> config.cpp:2:6
+  |
+2 | \tx = G(foo);
+  | \t    ^^^^^^ used here
+  |
+ ::: script_macros.hpp:1:9
+  |
+1 | #define G(a) a
+  |         - defined here
+  |
+  = help: include script_macros.hpp
+");
+    assert!(diagnostic.render(&sources, true).contains("\x1b[1;31merror[unknown-macro]\x1b[0m"));
+}
+
+#[test]
+fn diagnostics_serialize_to_json() {
+    let (sources, config, _) = sources();
+    let diagnostic = Diagnostic::warning("Quote \" and\nnewline.")
+        .with_label(Label::primary(FileSpan::new(config, 15..21), ""))
+        .with_note("a note");
+    assert_eq!(
+        diagnostic.to_json(&sources),
+        "{\"severity\":\"warning\",\"code\":null,\"message\":\"Quote \\\" and\\nnewline.\",\"labels\":[\
+         {\"file\":\"config.cpp\",\"file_id\":0,\"start\":15,\"end\":21,\"line\":2,\"column\":6,\
+         \"end_line\":2,\"end_column\":12,\"primary\":true,\"message\":\"\"}],\"notes\":[\"a note\"],\"help\":[]}"
+    );
+}
+
+#[test]
+fn preprocess_errors_convert_to_diagnostics() {
+    let mut preprocessor = BohemiaPreProcessor::with_resolver(MemoryFileSystem::new());
+    let main = preprocessor.sources_mut().add("main.cpp", "x = 1;\n#ifdef A\n");
+    let diagnostic = preprocessor.preprocess_file(main).unwrap_err().to_diagnostic();
+    assert_eq!(diagnostic.code.as_deref(), Some("unterminated-conditional"));
+    assert!(diagnostic.render(preprocessor.sources(), false).contains(" --> main.cpp:2:1\n"));
+}
@@ -0,0 +1,125 @@
+use bex::*;
+use proptest::prelude::*;
+
+#[derive(Debug, Clone)]
+struct Edit {
+    start:       usize,
+    len:         usize,
+    replacement: Vec<u8>
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+    (0..64usize, 0..8usize, prop::collection::vec(any::<u8>(), 0..8))
+        .prop_map(|(start, len, replacement)| Edit { start, len, replacement })
+}
+
+/// Clamps the range of an edit into the bounds of contents of the given length.
+fn clamp(edit: &Edit, len: usize) -> std::ops::Range<usize> {
+    let start = edit.start.min(len);
+    start..(start + edit.len).min(len)
+}
+
+#[test]
+fn edits_before_the_cursor_shift_it() {
+    let mut lexer = Lexer::from_slice(b"let x = 1;");
+    lexer.set_pos(8).unwrap();
+    lexer.replace_range(4..5, b"name").unwrap();
+    assert_eq!(lexer.contents(), b"let name = 1;");
+    assert_eq!(lexer.pos(), 11);
+    assert_eq!(lexer.peek().unwrap(), &b'1');
+    lexer.replace_range(0..4, b"").unwrap();
+    assert_eq!(lexer.pos(), 7);
+    assert_eq!(lexer.peek().unwrap(), &b'1');
+}
+
+#[test]
+fn edits_containing_the_cursor_move_it_to_their_start() {
+    let mut lexer = Lexer::from_slice(b"let x = 1;");
+    lexer.set_pos(6).unwrap();
+    lexer.replace_range(4..7, b"y =").unwrap();
+    assert_eq!(lexer.pos(), 4);
+    lexer.set_pos(9).unwrap();
+    lexer.replace_range(8..10, b"").unwrap();
+    assert_eq!(lexer.contents(), b"let y = ");
+    assert_eq!(lexer.pos(), 8);
+    assert!(lexer.is_end());
+}
+
+#[test]
+fn inserts_at_the_cursor_leave_it_in_place() {
+    let mut lexer = Lexer::from_slice(b"a;");
+    lexer.set_pos(1).unwrap();
+    lexer.insert_at(1, b" + b").unwrap();
+    assert_eq!(lexer.contents(), b"a + b;");
+    assert_eq!(lexer.pos(), 1);
+    assert_eq!(lexer.peek().unwrap(), &b' ');
+    lexer.insert_at(6, b"\n").unwrap();
+    assert_eq!(lexer.pos(), 1);
+}
+
+#[test]
+fn batched_edits_move_the_cursor_like_single_edits() {
+    let mut lexer = Lexer::from_slice(b"one two three");
+    lexer.set_pos(4).unwrap();
+    {
+        let mut batch = lexer.edit();
+        batch.replace_range(0..3, b"1").unwrap();
+        assert_eq!(batch.cursor(), 2);
+        batch.insert_at(2, b"2 ").unwrap();
+        assert_eq!(batch.cursor(), 2);
+        batch.replace_range(1..7, b"").unwrap();
+        assert_eq!(batch.cursor(), 1);
+    }
+    assert_eq!(lexer.contents(), b"1 three");
+    assert_eq!(lexer.pos(), 1);
+}
+
+proptest! {
+    #[test]
+    fn batched_edits_match_vec_splice(
+        initial in prop::collection::vec(any::<u8>(), 0..64),
+        edits in prop::collection::vec(edit(), 0..16)
+    ) {
+        let mut lexer = Lexer::from_slice(&initial);
+        let mut model = initial.clone();
+        {
+            let mut batch = lexer.edit();
+            for edit in &edits {
+                let range = clamp(edit, model.len());
+                let removed: Vec<u8> = model.splice(range.clone(), edit.replacement.iter().copied()).collect();
+                prop_assert_eq!(batch.replace_range(range, &edit.replacement).unwrap(), removed);
+                prop_assert_eq!(batch.len(), model.len());
+            }
+        }
+        prop_assert_eq!(lexer.contents(), &model[..]);
+    }
+
+    #[test]
+    fn single_edits_keep_cursor_on_same_element(
+        initial in prop::collection::vec(any::<u8>(), 1..64),
+        cursor in 0..64usize,
+        edit in edit()
+    ) {
+        let mut lexer = Lexer::new(&initial);
+        let cursor = cursor % initial.len();
+        lexer.set_pos(cursor).unwrap();
+        let std::ops::Range { start, end } = clamp(&edit, initial.len());
+        let removed = lexer.splice(start..end, edit.replacement.iter().copied()).unwrap();
+        prop_assert_eq!(&removed[..], &initial[start..end]);
+        if cursor < start || (cursor >= end && cursor > start) {
+            prop_assert_eq!(lexer.peek().unwrap(), &initial[cursor]);
+        } else {
+            prop_assert_eq!(lexer.pos(), start);
+        }
+    }
+
+    #[test]
+    fn out_of_bounds_ranges_are_rejected(
+        initial in prop::collection::vec(any::<u8>(), 0..16),
+        end in 17..32usize
+    ) {
+        let mut lexer = Lexer::new(&initial);
+        prop_assert!(lexer.extract(0..end).is_err());
+        prop_assert_eq!(lexer.contents(), &initial[..]);
+    }
+}
@@ -0,0 +1,53 @@
+use std::error::Error;
+use std::io;
+use bex::*;
+
+#[test]
+fn errors_convert_into_io_errors_of_a_matching_kind() {
+    let cases = [
+        (BexError::UnexpectedEof { pos: 3 }, io::ErrorKind::UnexpectedEof),
+        (BexError::unexpected(&'x', "a digit", 1..2), io::ErrorKind::InvalidData),
+        (BexError::InvalidUtf8 { span: 0..2 }, io::ErrorKind::InvalidData),
+        (BexError::CursorUnderflow { pos: 0, count: 1 }, io::ErrorKind::InvalidInput),
+        (BexError::RangeOutOfBounds { range: 2..9, len: 4 }, io::ErrorKind::InvalidInput),
+        (BexError::RewindPastWindow { pos: 1, window_start: 8 }, io::ErrorKind::InvalidInput)
+    ];
+    for (error, kind) in cases {
+        let message = error.to_string();
+        let converted = io::Error::from(error);
+        assert_eq!(converted.kind(), kind);
+        assert_eq!(converted.to_string(), message);
+        assert!(converted.get_ref().is_some_and(|it| it.is::<BexError>()));
+    }
+}
+
+#[test]
+fn io_errors_round_trip_unchanged() {
+    let error = BexError::from(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+    assert!(error.source().is_some());
+    assert_eq!(error.span(), None);
+    let converted = io::Error::from(error);
+    assert_eq!(converted.kind(), io::ErrorKind::PermissionDenied);
+    assert_eq!(converted.to_string(), "denied");
+}
+
+#[test]
+fn spans_point_at_where_the_error_was_raised() {
+    assert_eq!(BexError::UnexpectedEof { pos: 3 }.span(), Some(3..3));
+    assert_eq!(BexError::unexpected(&'x', "a digit", 1..2).span(), Some(1..2));
+    assert_eq!(BexError::InvalidUtf8 { span: 4..6 }.span(), Some(4..6));
+    assert_eq!(BexError::CursorUnderflow { pos: 2, count: 5 }.span(), Some(2..2));
+    assert_eq!(BexError::RangeOutOfBounds { range: 2..9, len: 4 }.span(), Some(2..9));
+    assert_eq!(BexError::RewindPastWindow { pos: 1, window_start: 8 }.span(), Some(1..1));
+}
+
+#[test]
+fn read_errors_carry_the_cursor_position() {
+    let mut lexer = Lexer::from_slice(b"ab");
+    lexer.advance(2).unwrap();
+    let error = lexer.get().unwrap_err();
+    assert_eq!(error.span(), Some(2..2));
+    assert_eq!(error.to_string(), "End of file was reached unexpectedly at offset 2.");
+    let error = Lexer::from_slice(b"ab").extract(1..5).unwrap_err();
+    assert_eq!(error.span(), Some(1..5));
+}
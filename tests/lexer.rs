@@ -0,0 +1,76 @@
+use std::borrow::Cow;
+use bex::*;
+
+#[derive(Debug, PartialEq)]
+enum Word {
+    Word(Vec<u8>),
+    Number(Vec<u8>)
+}
+
+impl Token<'_, u8> for Word {
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'_, u8>) -> Result<Self> {
+        let start = lexer.pos();
+        match *lexer.peek()? {
+            it if it.is_ascii_alphabetic() => Ok(Word::Word(lexer.take_while(u8::is_ascii_alphabetic)?)),
+            it if it.is_ascii_digit() => Ok(Word::Number(lexer.take_while(u8::is_ascii_digit)?)),
+            it => Err(BexError::unexpected(&(it as char), "a word or number", start..start + 1))
+        }
+    }
+
+    fn skip_ignored(lexer: &mut Lexer<'_, u8>) -> Result<()> {
+        lexer.skip_while(|it| *it == b' ')?;
+        Ok(())
+    }
+}
+
+#[test]
+fn borrowed_and_owned_lexers_read_the_same() {
+    let source = b"class A {};".to_vec();
+    let mut borrowed = Lexer::from_slice(&source);
+    let mut owned = Lexer::from_vec(source.clone());
+    assert!(borrowed.is_borrowed());
+    assert!(!owned.is_borrowed());
+    for lexer in [&mut borrowed, &mut owned] {
+        lexer.skip_while(u8::is_ascii_alphabetic).unwrap();
+        assert_eq!(lexer.get().unwrap(), b' ');
+    }
+    assert_eq!(borrowed.source_slice(0..5), Some(&source[0..5]));
+    assert_eq!(owned.source_slice(0..5), None);
+    assert!(matches!(borrowed.source_cow(6..7), Some(Cow::Borrowed(b"A"))));
+    assert!(matches!(owned.source_cow(6..7), Some(Cow::Owned(it)) if it == b"A"));
+    assert_eq!(borrowed.drain(), owned.drain());
+}
+
+#[test]
+fn editing_a_borrowed_lexer_copies_its_contents() {
+    let source = b"a = 1;";
+    let mut lexer = Lexer::from_slice(source);
+    lexer.replace_range(4..5, b"22").unwrap();
+    assert!(!lexer.is_borrowed());
+    assert_eq!(lexer.contents(), b"a = 22;");
+    assert_eq!(source, b"a = 1;");
+}
+
+#[test]
+fn spanned_tokens_cover_the_token_without_skipped_input() {
+    let tokens = Lexer::from_slice(b"ab 12  cd").tokenize_spanned::<Word>().unwrap();
+    assert_eq!(tokens, [
+        Spanned::new(Word::Word(b"ab".to_vec()), 0..2),
+        Spanned::new(Word::Number(b"12".to_vec()), 3..5),
+        Spanned::new(Word::Word(b"cd".to_vec()), 7..9)
+    ]);
+    assert_eq!(tokens[1].as_ref().map(|it| matches!(it, Word::Number(_))), Spanned::new(true, 3..5));
+}
+
+#[test]
+fn spans_start_at_the_cursor_and_stop_at_the_first_error() {
+    let mut lexer = Lexer::from_slice(b"skip ab");
+    lexer.set_pos(4).unwrap();
+    let tokens = lexer.tokenize_spanned::<Word>().unwrap();
+    assert_eq!(tokens, [Spanned::new(Word::Word(b"ab".to_vec()), 5..7)]);
+    assert!(Lexer::from_slice(b"").tokenize_spanned::<Word>().unwrap().is_empty());
+    let error = Lexer::from_slice(b"ab ?").tokenize_spanned::<Word>().unwrap_err();
+    assert_eq!(error.span(), Some(3..4));
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+use bex::*;
+
+#[test]
+fn crlf_line_endings_belong_to_the_line_ending() {
+    let lexer = Lexer::from_slice(b"ab\r\ncd\nef");
+    assert_eq!(lexer.line_col(2), Some(LineCol { line: 0, column: 2 }));
+    assert_eq!(lexer.line_col(3), Some(LineCol { line: 0, column: 2 }));
+    assert_eq!(lexer.line_col(4), Some(LineCol { line: 1, column: 0 }));
+    assert_eq!(lexer.line_col(7), Some(LineCol { line: 2, column: 0 }));
+    assert_eq!(lexer.line_index().line_range(0), Some(0..2));
+    assert_eq!(lexer.line_index().line_count(), 3);
+}
+
+#[test]
+fn columns_count_utf8_characters() {
+    let source = "é = \"ünï\";\nx";
+    let lexer = Lexer::from_slice(source.as_bytes());
+    let quote = source.find("ünï").unwrap();
+    assert_eq!(lexer.line_col(quote), Some(LineCol { line: 0, column: 5 }));
+    assert_eq!(lexer.line_col(source.find(';').unwrap()), Some(LineCol { line: 0, column: 9 }));
+    assert_eq!(lexer.line_col(source.len()), Some(LineCol { line: 1, column: 1 }));
+    assert_eq!(lexer.line_col(source.len() + 1), None);
+}
+
+#[test]
+fn offsets_round_trip_through_line_and_column() {
+    let source = "a\r\nñb\nc€d\n";
+    let lexer = Lexer::from_slice(source.as_bytes());
+    for (offset, _) in source.char_indices().chain([(source.len(), ' ')]) {
+        let location = lexer.line_col(offset).unwrap();
+        let expected = if source[offset..].starts_with("\n") && source[..offset].ends_with('\r') { offset - 1 } else { offset };
+        assert_eq!(lexer.line_index().offset(location), Some(expected), "offset {offset} at {location}");
+    }
+    assert_eq!(lexer.line_index().offset(LineCol { line: 1, column: 3 }), None);
+}
+
+#[test]
+fn location_follows_the_cursor() {
+    let mut lexer = Lexer::from_slice(b"one\ntwo");
+    lexer.seek_until(b't').unwrap();
+    assert_eq!(lexer.location(), Some(LineCol { line: 1, column: 0 }));
+    lexer.step_forward().unwrap();
+    assert_eq!(lexer.location(), Some(LineCol { line: 1, column: 1 }));
+}
+
+#[test]
+fn the_line_index_is_cached_until_the_contents_are_edited() {
+    let mut lexer = Lexer::from_slice(b"a\nb");
+    let index = lexer.line_index().clone();
+    assert!(Arc::ptr_eq(&index, lexer.line_index()));
+
+    lexer.insert_at(0, b"\n").unwrap();
+    assert!(!Arc::ptr_eq(&index, lexer.line_index()));
+    assert_eq!(lexer.line_index().line_count(), 3);
+    assert_eq!(lexer.line_col(3), Some(LineCol { line: 2, column: 0 }));
+}
@@ -0,0 +1,97 @@
+use bex::*;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Scope {
+    #[default]
+    Config,
+    String,
+    Code
+}
+
+#[derive(Debug, PartialEq)]
+enum Tok<'a> {
+    Quote,
+    Text(&'a [u8]),
+    Open,
+    Close,
+    Other(u8)
+}
+
+impl<'a> ScopedToken<'a, u8> for Tok<'a> {
+    type Scope = Scope;
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'a, u8>, scopes: &mut ModeStack<Scope>) -> Result<Self> {
+        let start = lexer.pos();
+        let byte = lexer.get()?;
+        Ok(match (*scopes.current(), byte) {
+            (Scope::String, b'"') => { scopes.pop(); Tok::Quote }
+            (Scope::String, _) => {
+                lexer.skip_while(|it| *it != b'"')?;
+                Tok::Text(lexer.source_slice(start..lexer.pos()).unwrap())
+            }
+            (_, b'"') => { scopes.push(Scope::String); Tok::Quote }
+            (_, b'{') => { scopes.push(Scope::Code); Tok::Open }
+            (Scope::Code, b'}') => { scopes.pop(); Tok::Close }
+            (_, it) => Tok::Other(it)
+        })
+    }
+}
+
+#[test]
+fn scopes_persist_between_tokens() {
+    let tokens = Lexer::from_slice(b"{\"a {b}\"}x").tokenize_until_end::<Tok>().unwrap();
+    assert_eq!(tokens, [Tok::Open, Tok::Quote, Tok::Text(b"a {b}"), Tok::Quote, Tok::Close, Tok::Other(b'x')]);
+}
+
+#[test]
+fn lexer_exposes_its_mode_stack() {
+    let mut lexer = Lexer::from_slice(b"{\"a");
+    <Tok as Token<u8>>::next_token(&mut lexer).unwrap();
+    <Tok as Token<u8>>::next_token(&mut lexer).unwrap();
+    let modes = lexer.modes::<Scope>().unwrap();
+    assert_eq!(modes.iter().copied().collect::<Vec<_>>(), [Scope::Config, Scope::Code, Scope::String]);
+    assert_eq!(modes.depth(), 2);
+
+    lexer.modes_mut::<Scope>().reset();
+    assert_eq!(<Tok as Token<u8>>::next_token(&mut lexer).unwrap(), Tok::Other(b'a'));
+    assert!(lexer.modes::<Scope>().unwrap().is_base());
+    assert_eq!(lexer.modes_mut::<Scope>().pop(), None);
+}
+
+#[test]
+fn rollback_restores_modes_pushed_since_the_checkpoint() {
+    let mut lexer = Lexer::from_slice(b"x\"a b\"");
+    <Tok as Token<u8>>::next_token(&mut lexer).unwrap();
+    let failed: Result<Tok> = lexer.try_with(|lexer| {
+        assert_eq!(<Tok as Token<u8>>::next_token(lexer)?, Tok::Quote);
+        Err(BexError::UnexpectedEof { pos: lexer.pos() })
+    });
+    assert!(failed.is_err());
+    assert_eq!(lexer.pos(), 1);
+    assert!(lexer.modes::<Scope>().is_some_and(ModeStack::is_base));
+    assert_eq!(<Tok as Token<u8>>::next_token(&mut lexer).unwrap(), Tok::Quote);
+    assert_eq!(<Tok as Token<u8>>::next_token(&mut lexer).unwrap(), Tok::Text(b"a b"));
+}
+
+#[test]
+fn token_streams_restore_modes_of_buffered_tokens() {
+    let mut stream = Lexer::from_slice(b"\"a\" b").token_stream::<Tok>();
+    assert!(matches!(stream.peek_nth(2), Some(Ok(Tok::Quote))));
+    let mut lexer = stream.into_lexer();
+    assert_eq!(lexer.pos(), 0);
+    assert!(lexer.modes::<Scope>().is_none_or(ModeStack::is_base));
+    assert_eq!(<Tok as Token<u8>>::next_token(&mut lexer).unwrap(), Tok::Quote);
+    assert_eq!(<Tok as Token<u8>>::next_token(&mut lexer).unwrap(), Tok::Text(b"a"));
+}
+
+#[test]
+fn lexers_with_modes_can_move_to_another_thread() {
+    fn is_send<T: Send>() {}
+    is_send::<Lexer<'static, u8>>();
+
+    let mut lexer = Lexer::from_vec(b"\"a\" {b}".to_vec());
+    lexer.modes_mut::<Scope>().push(Scope::Code);
+    let depth = std::thread::spawn(move || lexer.modes::<Scope>().map(ModeStack::depth)).join().unwrap();
+    assert_eq!(depth, Some(1));
+}
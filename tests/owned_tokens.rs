@@ -0,0 +1,60 @@
+use bex::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Equals,
+    Semicolon
+}
+
+fn ident(name: &str) -> Tok { Tok::Ident(name.to_string()) }
+
+fn tokens() -> Vec<Tok> {
+    vec![ident("name"), Tok::Equals, Tok::Str("value".to_string()), Tok::Semicolon, ident("other"), Tok::Semicolon]
+}
+
+#[test]
+fn lexer_walks_owned_tokens() {
+    let mut lexer = Lexer::new(tokens());
+    assert_eq!(lexer.peek().unwrap(), &ident("name"));
+    assert_eq!(lexer.get_cloned().unwrap(), ident("name"));
+    assert!(lexer.take(&Tok::Equals).unwrap());
+    assert!(!lexer.take(&Tok::Equals).unwrap());
+    assert!(matches!(lexer.get_cloned().unwrap(), Tok::Str(it) if it == "value"));
+    lexer.skip_while(|it| *it != ident("other")).unwrap();
+    assert_eq!(lexer.pos(), 4);
+    assert_eq!(lexer.skip_while(|it| matches!(it, Tok::Ident(_))).unwrap(), 1);
+    assert!(lexer.peek_seq(&[Tok::Semicolon]).unwrap());
+    assert_eq!(lexer.get_cloned().unwrap(), Tok::Semicolon);
+    assert!(lexer.is_end());
+    assert!(lexer.get_cloned().is_err());
+}
+
+#[test]
+fn combinators_parse_owned_tokens() {
+    let mut lexer = Lexer::new(tokens());
+    let name = satisfy("an identifier", |it: &Tok| matches!(it, Tok::Ident(_)));
+    let value = satisfy("a value", |it: &Tok| matches!(it, Tok::Ident(_) | Tok::Str(_)));
+    let mut property = seq((name, token(Tok::Equals), value, token(Tok::Semicolon)));
+    let (name, _, value, _) = property(&mut lexer).unwrap();
+    assert_eq!(name, ident("name"));
+    assert_eq!(value, Tok::Str("value".to_string()));
+
+    let error = property(&mut lexer).unwrap_err();
+    assert_eq!(error.pos, 5);
+    assert_eq!(error.expected, ["Equals"]);
+}
+
+#[test]
+fn value_returning_helpers_clone_owned_tokens() {
+    let mut lexer = Lexer::new(tokens());
+    assert_eq!(lexer.take_if(|it| matches!(it, Tok::Ident(_))).unwrap(), Some(ident("name")));
+    assert_eq!(lexer.take_if(|it| matches!(it, Tok::Ident(_))).unwrap(), None);
+    assert_eq!(lexer.get_until(Tok::Semicolon).unwrap(), [Tok::Equals, Tok::Str("value".to_string())]);
+    assert_eq!(lexer.get_not(Tok::Semicolon).unwrap(), ident("other"));
+    lexer.reset().unwrap();
+    assert_eq!(lexer.take_while(|it| *it != Tok::Semicolon).unwrap().len(), 3);
+    lexer.step_forward().unwrap();
+    assert_eq!(lexer.get_until_seq(&[Tok::Semicolon]).unwrap(), [ident("other")]);
+}
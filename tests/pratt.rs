@@ -0,0 +1,140 @@
+use bex::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tok {
+    Number(u8),
+    Plus,
+    Minus,
+    Star,
+    Caret,
+    Bang,
+    LParen,
+    RParen
+}
+
+impl Token<'_, u8> for Tok {
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'_, u8>) -> Result<Self> {
+        let start = lexer.pos();
+        Ok(match lexer.get()? {
+            b'+' => Tok::Plus,
+            b'-' => Tok::Minus,
+            b'*' => Tok::Star,
+            b'^' => Tok::Caret,
+            b'!' => Tok::Bang,
+            b'(' => Tok::LParen,
+            b')' => Tok::RParen,
+            it if it.is_ascii_digit() => Tok::Number(it - b'0'),
+            it => return Err(BexError::unexpected(&(it as char), "a token", start..start + 1))
+        })
+    }
+}
+
+fn symbol(token: Tok) -> &'static str {
+    match token {
+        Tok::Plus => "+",
+        Tok::Minus => "-",
+        Tok::Star => "*",
+        Tok::Caret => "^",
+        Tok::Bang => "!",
+        _ => "?"
+    }
+}
+
+fn parser() -> PrattParser<Tok, String> {
+    PrattParser::new()
+        .infix(Tok::Plus, 1, Associativity::Left, |l, op, r| format!("({l} {} {r})", symbol(op)))
+        .infix(Tok::Minus, 1, Associativity::Left, |l, op, r| format!("({l} {} {r})", symbol(op)))
+        .infix(Tok::Star, 2, Associativity::Left, |l, op, r| format!("({l} {} {r})", symbol(op)))
+        .prefix(Tok::Minus, 3, |op, it| format!("({}{it})", symbol(op)))
+        .infix(Tok::Caret, 4, Associativity::Right, |l, op, r| format!("({l} {} {r})", symbol(op)))
+        .postfix(Tok::Bang, 5, |it, op| format!("({it}{})", symbol(op)))
+}
+
+fn parse(source: &str) -> String {
+    let tokens: Vec<Tok> = Lexer::from_slice(source.as_bytes()).tokenize_until_end().unwrap();
+    let mut lexer = Lexer::new(tokens);
+    let pratt = parser();
+    let expression = pratt.parse(&mut lexer, |input: &mut Lexer<Tok>| operand(&pratt, input)).unwrap();
+    assert!(lexer.is_end(), "unparsed input in {source}");
+    expression
+}
+
+fn operand(pratt: &PrattParser<Tok, String>, input: &mut Lexer<Tok>) -> Result<String> {
+    match input.get()? {
+        Tok::Number(it) => Ok(it.to_string()),
+        Tok::LParen => {
+            let inner = pratt.parse(input, |input: &mut Lexer<Tok>| operand(pratt, input))?;
+            if !input.take(&Tok::RParen)? { return Err(BexError::unexpected(input.peek()?, "`)`", input.pos()..input.pos() + 1)) }
+            Ok(inner)
+        }
+        it => Err(BexError::unexpected(&it, "an operand", input.pos() - 1..input.pos()))
+    }
+}
+
+#[test]
+fn precedence_and_associativity() {
+    assert_eq!(parse("1+2*3"), "(1 + (2 * 3))");
+    assert_eq!(parse("1-2-3"), "((1 - 2) - 3)");
+    assert_eq!(parse("2^3^4"), "(2 ^ (3 ^ 4))");
+    assert_eq!(parse("(1+2)*3"), "((1 + 2) * 3)");
+}
+
+#[test]
+fn prefix_and_postfix_operators() {
+    assert_eq!(parse("-1-2"), "((-1) - 2)");
+    assert_eq!(parse("1--2"), "(1 - (-2))");
+    assert_eq!(parse("-2^2"), "(-(2 ^ 2))");
+    assert_eq!(parse("-3!"), "(-(3!))");
+    assert_eq!(parse("2*3!!"), "(2 * ((3!)!))");
+}
+
+#[test]
+fn prefix_operands_stop_at_infix_operators_of_the_same_precedence() {
+    let pratt = PrattParser::new()
+        .infix(Tok::Minus, 1, Associativity::Left, |l, op, r| format!("({l} {} {r})", symbol(op)))
+        .prefix(Tok::Minus, 1, |op, it| format!("({}{it})", symbol(op)));
+    let mut lexer = Lexer::new(Lexer::from_slice(b"-1-2").tokenize_until_end::<Tok>().unwrap());
+    let expression = pratt.parse(&mut lexer, |input: &mut Lexer<Tok>| operand(&pratt, input)).unwrap();
+    assert_eq!(expression, "((-1) - 2)");
+    assert!(lexer.is_end());
+}
+
+#[test]
+fn tokens_in_both_tables_are_infix_where_postfix_binds_too_loosely() {
+    let pratt = PrattParser::new()
+        .infix(Tok::Star, 2, Associativity::Left, |l, op, r| format!("({l} {} {r})", symbol(op)))
+        .postfix(Tok::Bang, 0, |it, op| format!("({it}{})", symbol(op)))
+        .infix(Tok::Bang, 5, Associativity::Left, |l, op, r| format!("({l} {} {r})", symbol(op)));
+    let parse = |source: &[u8]| {
+        let mut lexer = Lexer::new(Lexer::from_slice(source).tokenize_until_end::<Tok>().unwrap());
+        let expression = pratt.parse(&mut lexer, |input: &mut Lexer<Tok>| operand(&pratt, input)).unwrap();
+        assert!(lexer.is_end());
+        expression
+    };
+    assert_eq!(parse(b"1*2!3"), "(1 * (2 ! 3))");
+    assert_eq!(parse(b"2!"), "(2!)");
+}
+
+#[test]
+fn parses_from_a_token_stream() {
+    let mut stream = Lexer::from_slice(b"1+2*3)").token_stream::<Tok>();
+    let expression = parser().parse(&mut stream, |input: &mut TokenStream<Tok, u8>| match input.next() {
+        Some(Ok(Tok::Number(it))) => Ok(it.to_string()),
+        _ => Err(BexError::UnexpectedEof { pos: 0 })
+    }).unwrap();
+    assert_eq!(expression, "(1 + (2 * 3))");
+    assert!(matches!(stream.next(), Some(Ok(Tok::RParen))));
+}
+
+#[test]
+fn token_stream_errors_are_returned() {
+    let mut stream = Lexer::from_slice(b"1+?").token_stream::<Tok>();
+    let error = parser().parse(&mut stream, |input: &mut TokenStream<Tok, u8>| match input.next() {
+        Some(Ok(Tok::Number(it))) => Ok(it.to_string()),
+        Some(Err(error)) => Err(error),
+        _ => Err(BexError::UnexpectedEof { pos: 0 })
+    }).unwrap_err();
+    assert!(matches!(error, BexError::Unexpected { .. }));
+}
@@ -0,0 +1,123 @@
+use bex::*;
+
+const SCRIPT_MACROS: &str = "#define PREFIX cba
+#define COMPONENT main
+#define DOUBLES(var1,var2) var1##_##var2
+#define TRIPLES(var1,var2,var3) var1##_##var2##_##var3
+#define QUOTE(var1) #var1
+#define GVAR(var1) TRIPLES(PREFIX,COMPONENT,var1)
+#define FUNC(var1) TRIPLES(PREFIX,DOUBLES(COMPONENT,fnc),var1)
+";
+
+fn preprocess(files: &[(&str, &str)], main: &str) -> Result<Preprocessed<u8>, PreprocessError> {
+    let mut file_system = MemoryFileSystem::new();
+    for (path, contents) in files {
+        file_system.insert(path, *contents);
+    }
+    let mut preprocessor = BohemiaPreProcessor::with_resolver(file_system);
+    preprocessor.preprocess("\\x\\cba\\addons\\main\\config.cpp".to_string(), Lexer::from_slice(main.as_bytes()))
+}
+
+fn output(files: &[(&str, &str)], main: &str) -> String {
+    String::from_utf8(preprocess(files, main).unwrap().output).unwrap()
+}
+
+#[test]
+fn arguments_are_expanded_before_pasting_and_stringification() {
+    let out = output(
+        &[("\\x\\cba\\addons\\main\\script_macros.hpp", SCRIPT_MACROS)],
+        "#include \"script_macros.hpp\"\nx = QUOTE(call FUNC(test));\ny = GVAR(foo);\n"
+    );
+    assert!(out.contains("x = \"call cba_main_fnc_test\";"));
+    assert!(out.contains("y = cba_main_foo;"));
+}
+
+#[test]
+fn strings_and_comments_are_not_expanded() {
+    let out = output(&[], "#define A 1\nx = \"A\"; // A\ny = A; /* A\n*/ z = A;\n");
+    assert_eq!(out, "\nx = \"A\"; \ny = 1; \n z = 1;\n");
+}
+
+#[test]
+fn conditionals_keep_line_numbers_aligned() {
+    let source = "#define A\n#ifdef A\nyes\n#else\nno\n#endif\n#ifndef A\nno\n#endif\nline = __LINE__;\n";
+    let out = output(&[], source);
+    assert_eq!(out.lines().count(), source.lines().count());
+    assert!(out.contains("yes") && !out.contains("no"));
+    assert!(out.contains("line = 10;"));
+}
+
+#[test]
+fn line_continuations_join_definitions() {
+    let out = output(&[], "#define SUM(a) \\\n  a + \\\n  a\nx = SUM(2);\n#undef SUM\ny = SUM(2);\n");
+    assert!(out.contains("x = 2 +   2;"));
+    assert!(out.contains("y = SUM(2);"));
+}
+
+#[test]
+fn brackets_do_not_group_arguments() {
+    let error = preprocess(&[], "#define ONE(a) a\nx = ONE([1,2]);\n").unwrap_err();
+    assert!(matches!(error.kind, PreprocessErrorKind::ArgumentCount { expected: 1, found: 2, .. }));
+}
+
+#[test]
+fn expansions_resolve_to_their_call_site() {
+    let source = "#define G(x) pre_##x\nclass A {\n  y = G(foo);\n};\n";
+    let result = preprocess(&[], source).unwrap();
+    let text = String::from_utf8(result.output.clone()).unwrap();
+    let (origin, location) = result.source_map.resolve_location(text.find("pre_foo").unwrap()).unwrap();
+    assert_eq!(&source[origin.span.range], "G(foo)");
+    assert_eq!(origin.expansions[0].name, "G");
+    assert_eq!(location, Some(LineCol { line: 2, column: 6 }));
+}
+
+#[test]
+fn malformed_input_is_reported() {
+    assert!(matches!(preprocess(&[], "#ifdef X\n").unwrap_err().kind, PreprocessErrorKind::UnterminatedConditional));
+    assert!(matches!(preprocess(&[], "#endif\n").unwrap_err().kind, PreprocessErrorKind::UnmatchedConditional(_)));
+    assert!(matches!(preprocess(&[], "#foo\n").unwrap_err().kind, PreprocessErrorKind::UnknownDirective(_)));
+    assert!(matches!(preprocess(&[], "#include \"x.hpp\"\n").unwrap_err().kind, PreprocessErrorKind::Include { .. }));
+    assert!(matches!(preprocess(&[], "/* x").unwrap_err().kind, PreprocessErrorKind::UnterminatedComment));
+}
+
+#[test]
+fn absolute_includes_are_resolved_from_the_root() {
+    let out = output(
+        &[("\\x\\cba\\addons\\main\\script_macros.hpp", SCRIPT_MACROS)],
+        "#include \"\\X\\CBA\\addons\\main\\script_macros.hpp\"\ny = GVAR(foo);\n"
+    );
+    assert!(out.contains("y = cba_main_foo;"));
+}
+
+#[test]
+fn stringification_doubles_embedded_quotes() {
+    let out = output(&[], "#define S(a) #a\nx = S(\"x\");\ny = S(say \"hi\" now);\n");
+    assert_eq!(out, "\nx = \"\"\"x\"\"\";\ny = \"say \"\"hi\"\" now\";\n");
+}
+
+#[test]
+fn if_conditions_see_the_line_of_the_directive() {
+    let out = output(&[], "#if __LINE__\nfirst\n#else\nnone\n#endif\n");
+    assert_eq!(out.trim(), "first");
+    let out = output(&[], "#define ON 1\n#if ON\nyes\n#endif\n#if 0\nno\n#endif\n");
+    assert_eq!(out.trim(), "yes");
+}
+
+#[test]
+fn errors_in_macro_text_point_at_the_call_site() {
+    let source = "#define C(a,b) a##b\ny = C(/,*);\n";
+    let error = preprocess(&[], source).unwrap_err();
+    assert!(matches!(error.kind, PreprocessErrorKind::UnterminatedComment));
+    assert_eq!(&source[error.span.unwrap().range], "C(/,*)");
+
+    let source = "#define F(x) x\n#define G F(\nz = G;\n";
+    let error = preprocess(&[], source).unwrap_err();
+    assert!(matches!(error.kind, PreprocessErrorKind::UnterminatedArguments(ref name) if name == "F"));
+    assert_eq!(&source[error.span.unwrap().range], "G");
+
+    let mut preprocessor = BohemiaPreProcessor::with_resolver(MemoryFileSystem::new());
+    preprocessor.define("OPEN", "x /* y");
+    let error = preprocessor.preprocess("main.cpp".to_string(), Lexer::from_slice(b"a;\nb = OPEN;\n")).unwrap_err();
+    assert!(matches!(error.kind, PreprocessErrorKind::UnterminatedComment));
+    assert_eq!(error.span.map(|it| it.range), Some(7..11));
+}
@@ -0,0 +1,137 @@
+use bex::*;
+
+/// Reads `class Name;` or `class Name: Base {}`, returning the base if there is one.
+fn class_with_base(lexer: &mut Lexer<u8>) -> Result<Vec<u8>> {
+    if !lexer.peek_seq(b"class ")? { return Err(BexError::unexpected(lexer.peek()?, "`class`", lexer.pos()..lexer.pos() + 1)) }
+    lexer.advance(6)?;
+    lexer.take_while(u8::is_ascii_alphanumeric)?;
+    if !lexer.take(&b':')? { return Err(BexError::unexpected(lexer.peek()?, "`:`", lexer.pos()..lexer.pos() + 1)) }
+    lexer.skip_while(|it| *it == b' ')?;
+    lexer.take_while(u8::is_ascii_alphanumeric)
+}
+
+#[test]
+fn try_with_restores_the_cursor_on_err() {
+    let mut lexer = Lexer::from_slice(b"class A;");
+    let result = lexer.try_with(class_with_base);
+    assert!(matches!(result, Err(BexError::Unexpected { span, .. }) if span == (7..8)));
+    assert_eq!(lexer.pos(), 0);
+
+    let base = lexer.try_with(|lexer| class_with_base(lexer).ok());
+    assert_eq!(base, None);
+    assert_eq!(lexer.pos(), 0);
+}
+
+#[test]
+fn try_with_keeps_the_cursor_on_success() {
+    let mut lexer = Lexer::from_slice(b"class A: B {}");
+    assert_eq!(lexer.try_with(class_with_base).unwrap(), b"B");
+    assert_eq!(lexer.pos(), 10);
+    assert_eq!(lexer.try_with(|lexer| lexer.take_if(|it| *it == b' ').ok().flatten()), Some(b' '));
+    assert_eq!(lexer.pos(), 11);
+}
+
+#[test]
+fn rollback_returns_to_the_checkpoint() {
+    let mut lexer = Lexer::from_slice(b"abc");
+    lexer.step_forward().unwrap();
+    let checkpoint = lexer.checkpoint();
+    assert_eq!(checkpoint.pos(), 1);
+    lexer.advance(2).unwrap();
+    lexer.rollback(checkpoint.clone()).unwrap();
+    assert_eq!(lexer.pos(), 1);
+    lexer.reset().unwrap();
+    lexer.rollback(checkpoint).unwrap();
+    assert_eq!(lexer.get().unwrap(), b'b');
+}
+
+#[test]
+fn guards_restore_the_cursor_unless_committed() {
+    let mut lexer = Lexer::from_slice(b"class A;");
+    {
+        let mut guard = lexer.guard();
+        guard.advance(6).unwrap();
+        assert_eq!(guard.get().unwrap(), b'A');
+    }
+    assert_eq!(lexer.pos(), 0);
+
+    let mut guard = lexer.guard();
+    guard.advance(6).unwrap();
+    guard.commit();
+    assert_eq!(lexer.pos(), 6);
+}
+
+#[test]
+fn take_while_and_skip_while_stop_at_the_first_mismatch_or_the_end() {
+    let mut lexer = Lexer::from_slice(b"abc123");
+    assert_eq!(lexer.take_while(u8::is_ascii_alphabetic).unwrap(), b"abc");
+    assert_eq!(lexer.take_while(u8::is_ascii_alphabetic).unwrap(), b"");
+    assert_eq!(lexer.skip_while(u8::is_ascii_digit).unwrap(), 3);
+    assert!(lexer.is_end());
+    assert_eq!(lexer.take_while(|_| true).unwrap(), b"");
+    assert_eq!(lexer.skip_while(|_| true).unwrap(), 0);
+}
+
+#[test]
+fn seek_until_any_stops_on_the_first_target() {
+    let mut lexer = Lexer::from_slice(b"key = value;");
+    lexer.seek_until_any(b"=;").unwrap();
+    assert_eq!(lexer.pos(), 4);
+    lexer.seek_until_any(b"=;").unwrap();
+    assert_eq!(lexer.pos(), 4);
+    lexer.step_forward().unwrap();
+    lexer.seek_until_any(b"=;").unwrap();
+    assert_eq!(lexer.pos(), 11);
+    lexer.step_forward().unwrap();
+    assert!(matches!(lexer.seek_until_any(b"=;"), Err(BexError::UnexpectedEof { pos: 12 })));
+}
+
+#[test]
+fn peek_seq_never_moves_the_cursor() {
+    let mut lexer = Lexer::from_slice(b"/* c */");
+    assert!(lexer.peek_seq(b"/*").unwrap());
+    assert!(!lexer.peek_seq(b"//").unwrap());
+    assert!(lexer.peek_seq(b"").unwrap());
+    assert_eq!(lexer.pos(), 0);
+    lexer.set_pos(5).unwrap();
+    assert!(lexer.peek_seq(b"*/").unwrap());
+    assert!(!lexer.peek_seq(b"*/ and more").unwrap());
+    assert_eq!(lexer.pos(), 5);
+}
+
+#[test]
+fn get_until_seq_stops_at_the_start_of_the_terminator() {
+    let mut lexer = Lexer::from_slice(b"a * b */ c");
+    assert_eq!(lexer.get_until_seq(b"*/").unwrap(), b"a * b ");
+    assert_eq!(lexer.pos(), 6);
+    assert_eq!(lexer.get_until_seq(b"*/").unwrap(), b"");
+    lexer.advance(2).unwrap();
+    assert!(matches!(lexer.get_until_seq(b"*/"), Err(BexError::UnexpectedEof { pos: 10 })));
+}
+
+#[test]
+fn retreating_before_the_start_is_an_error() {
+    let mut lexer = Lexer::from_slice(b"abc");
+    assert!(matches!(lexer.step_back(), Err(BexError::CursorUnderflow { pos: 0, count: 1 })));
+    lexer.advance(2).unwrap();
+    let error = lexer.retreat(3).unwrap_err();
+    assert!(matches!(error, BexError::CursorUnderflow { pos: 2, count: 3 }));
+    assert_eq!(error.span(), Some(2..2));
+    assert_eq!(lexer.pos(), 2);
+    lexer.retreat(2).unwrap();
+    assert_eq!(lexer.pos(), 0);
+}
+
+#[test]
+fn advancing_past_the_end_is_an_error() {
+    let mut lexer = Lexer::from_slice(b"abc");
+    lexer.advance(3).unwrap();
+    assert!(lexer.is_end());
+    let error = lexer.step_forward().unwrap_err();
+    assert!(matches!(error, BexError::CursorOutOfBounds { pos: 4, len: 3 }));
+    assert_eq!(error.span(), Some(4..4));
+    assert_eq!(lexer.pos(), 3);
+    lexer.reset().unwrap();
+    assert!(matches!(lexer.advance(usize::MAX), Err(BexError::CursorOutOfBounds { pos: usize::MAX, len: 3 })));
+    assert_eq!(lexer.pos(), 0);
+}
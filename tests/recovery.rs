@@ -0,0 +1,78 @@
+use bex::*;
+
+#[derive(Debug, PartialEq)]
+enum Tok<'a> {
+    Word(&'a [u8]),
+    Str(&'a [u8]),
+    Space
+}
+
+impl<'a> Token<'a, u8> for Tok<'a> {
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'a, u8>) -> Result<Self> {
+        let start = lexer.pos();
+        match lexer.get()? {
+            b' ' | b'\n' => Ok(Tok::Space),
+            b'"' => {
+                while lexer.get()? != b'"' {
+                    if lexer.contents()[lexer.pos() - 1] == b'\n' {
+                        return Err(BexError::unexpected(&'\n', "closing quote", lexer.pos() - 1..lexer.pos()))
+                    }
+                }
+                Ok(Tok::Str(lexer.source_slice(start + 1..lexer.pos() - 1).unwrap()))
+            }
+            it if it.is_ascii_alphabetic() => {
+                lexer.skip_while(u8::is_ascii_alphabetic)?;
+                Ok(Tok::Word(lexer.source_slice(start..lexer.pos()).unwrap()))
+            }
+            it => Err(BexError::unexpected(&(it as char), "a token", start..start + 1))
+        }
+    }
+}
+
+/// Reads the same tokens, but skips spaces with `skip_ignored`
+#[derive(Debug, PartialEq)]
+struct Spaced<'a>(Tok<'a>);
+
+impl<'a> Token<'a, u8> for Spaced<'a> {
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'a, u8>) -> Result<Self> { Tok::next_token(lexer).map(Spaced) }
+
+    fn skip_ignored(lexer: &mut Lexer<'a, u8>) -> Result<()> {
+        lexer.skip_while(|it| *it == b' ')?;
+        Ok(())
+    }
+}
+
+#[test]
+fn every_bad_string_is_reported() {
+    let source = b"a \"bad\nb \"ok\" \"also bad\nc \"again";
+    let (tokens, errors) = Lexer::from_slice(source)
+        .tokenize_recovering::<Tok, _>(SkipUntil(|it: &u8| *it == b'\n'));
+    let words: Vec<_> = tokens.iter().filter(|it| **it != Tok::Space).collect();
+    assert_eq!(words, [&Tok::Word(b"a"), &Tok::Word(b"b"), &Tok::Str(b"ok"), &Tok::Word(b"c")]);
+    let spans: Vec<_> = errors.iter().map(|it| &source[it.span.clone()]).collect();
+    assert_eq!(spans, [&b"\"bad"[..], b"\"also bad", b"\"again"]);
+    assert_eq!(errors.iter().map(|it| it.span.clone()).collect::<Vec<_>>(), [2..6, 14..23, 26..32]);
+    assert!(matches!(errors[2].value, BexError::UnexpectedEof { .. }));
+}
+
+#[test]
+fn recovery_always_makes_progress() {
+    let (tokens, errors) = Lexer::from_slice(b"a?!b")
+        .tokenize_recovering::<Tok, _>(|_: &mut Lexer<u8>, _: usize| {});
+    assert_eq!(tokens, [Tok::Word(b"a"), Tok::Word(b"b")]);
+    assert_eq!(errors.iter().map(|it| it.span.clone()).collect::<Vec<_>>(), [1..2, 2..3]);
+
+    let (_, errors) = Lexer::from_slice(b"?!").tokenize_recovering::<Tok, _>(SkipElement);
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn error_spans_exclude_skipped_input() {
+    let (tokens, errors) = Lexer::from_slice(b"a  ?  b   !").tokenize_recovering::<Spaced, _>(SkipElement);
+    assert_eq!(tokens, [Spaced(Tok::Word(b"a")), Spaced(Tok::Word(b"b"))]);
+    assert_eq!(errors.iter().map(|it| it.span.clone()).collect::<Vec<_>>(), [3..4, 10..11]);
+}
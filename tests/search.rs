@@ -0,0 +1,78 @@
+use bex::*;
+use proptest::prelude::*;
+
+#[test]
+fn searches_stop_at_the_match() {
+    let mut lexer = Lexer::from_slice(b"a /* b */ \"c\"\n");
+    lexer.seek_bytes(b"*/").unwrap();
+    assert_eq!(lexer.pos(), 7);
+    assert_eq!(lexer.get_until_byte(b'"').unwrap(), b"*/ ");
+    lexer.step_forward().unwrap();
+    assert_eq!(lexer.get_until_bytes(b"\"\n").unwrap(), b"c");
+    lexer.reset().unwrap();
+    lexer.seek_any_byte(b"\"/").unwrap();
+    assert_eq!(lexer.pos(), 2);
+}
+
+#[test]
+fn generic_seek_until_advances_to_the_target() {
+    let mut lexer = Lexer::from_slice(b"ab;c");
+    lexer.seek_until(b';').unwrap();
+    assert_eq!(lexer.pos(), 2);
+    assert!(matches!(lexer.seek_until(b'x'), Err(BexError::UnexpectedEof { pos: 4 })));
+}
+
+#[test]
+fn missing_targets_leave_the_cursor_at_the_end() {
+    let mut lexer = Lexer::from_slice(b"abc");
+    assert!(matches!(lexer.seek_bytes(b"*/"), Err(BexError::UnexpectedEof { pos: 3 })));
+    assert!(lexer.is_end());
+    lexer.reset().unwrap();
+    assert!(lexer.get_until_byte(b'x').is_err());
+    assert_eq!(lexer.pos(), 3);
+}
+
+#[test]
+fn skips_stop_at_the_match_or_the_end() {
+    let mut lexer = Lexer::from_slice(b"// note\n\"text");
+    assert_eq!(lexer.skip_until_byte(b'\n').unwrap(), 7);
+    assert_eq!(lexer.peek().unwrap(), &b'\n');
+    assert_eq!(lexer.skip_until_byte(b'\n').unwrap(), 0);
+    lexer.advance(2).unwrap();
+    assert_eq!(lexer.skip_until_any_byte(b"\"\n").unwrap(), 4);
+    assert!(lexer.is_end());
+    assert_eq!(lexer.skip_until_any_byte(b"\"\n").unwrap(), 0);
+    assert_eq!(lexer.skip_until_any_byte(b"").unwrap(), 0);
+}
+
+proptest! {
+    #[test]
+    fn fast_paths_match_the_generic_ones(
+        source in proptest::collection::vec(prop_oneof![Just(b'*'), Just(b'/'), Just(b'"'), Just(b'a'), Just(b'b')], 0..64),
+        targets in proptest::collection::vec(prop_oneof![Just(b'*'), Just(b'/'), Just(b'"'), Just(b'b')], 1..5),
+        start in 0usize..64
+    ) {
+        let start = start.min(source.len());
+        let search = |f: &dyn Fn(&mut Lexer<u8>) -> Result<Vec<u8>>| {
+            let mut lexer = Lexer::from_slice(&source);
+            lexer.set_pos(start).unwrap();
+            let result = f(&mut lexer).ok();
+            (result, lexer.pos())
+        };
+        let generic = search(&|it| it.get_until(targets[0]));
+        let fast = search(&|it| it.get_until_byte(targets[0]));
+        prop_assert_eq!(generic, fast);
+        let generic = search(&|it| it.get_until_seq(&targets));
+        let fast = search(&|it| it.get_until_bytes(&targets));
+        prop_assert_eq!(generic, fast);
+        let generic = search(&|it| it.seek_until_any(&targets).map(|_| vec![]));
+        let fast = search(&|it| it.seek_any_byte(&targets).map(|_| vec![]));
+        prop_assert_eq!(generic, fast);
+        let generic = search(&|it| it.skip_while(|byte| *byte != targets[0]).map(|count| vec![0; count]));
+        let fast = search(&|it| it.skip_until_byte(targets[0]).map(|count| vec![0; count]));
+        prop_assert_eq!(generic, fast);
+        let generic = search(&|it| it.skip_while(|byte| !targets.contains(byte)).map(|count| vec![0; count]));
+        let fast = search(&|it| it.skip_until_any_byte(&targets).map(|count| vec![0; count]));
+        prop_assert_eq!(generic, fast);
+    }
+}
@@ -0,0 +1,130 @@
+use std::cell::Cell;
+use bex::*;
+
+/// Counts reads and lookups so caching can be observed
+struct CountingFileSystem {
+    inner:   MemoryFileSystem,
+    reads:   Cell<usize>,
+    lookups: Cell<usize>
+}
+
+impl FileSystem for CountingFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.reads.set(self.reads.get() + 1);
+        self.inner.read(path)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.lookups.set(self.lookups.get() + 1);
+        self.inner.exists(path)
+    }
+}
+
+#[test]
+fn files_are_deduplicated_by_path() {
+    let mut sources: SourceDb = SourceDb::new();
+    let first = sources.add("\\x\\cba\\addons\\main\\config.cpp", "a");
+    let other = sources.add("\\x\\cba\\addons\\main\\script_component.hpp", "b");
+    let again = sources.add("/X/CBA/addons/main/config.cpp", "c");
+    assert_eq!(first, again);
+    assert_ne!(first, other);
+    assert_eq!(sources.len(), 2);
+    assert_eq!(sources[first].contents(), b"c");
+    assert_eq!(sources.file_id("x/cba/addons/main/script_component.hpp"), Some(other));
+}
+
+#[test]
+fn lexers_are_tagged_with_their_file() {
+    let mut sources: SourceDb = SourceDb::new();
+    let file = sources.add("main.cpp", "class A {};");
+    let mut lexer = sources.lexer(file).unwrap();
+    assert_eq!(lexer.file(), Some(file));
+    assert!(lexer.is_borrowed());
+    lexer.set_pos(6).unwrap();
+    assert_eq!(lexer.file_span(6..7), Some(FileSpan::new(file, 6..7)));
+    assert_eq!(sources[file].line_col(6), Some(LineCol { line: 0, column: 6 }));
+}
+
+#[test]
+fn opened_files_are_read_once() {
+    let file_system = CountingFileSystem {
+        inner: MemoryFileSystem::new().with_file("\\x\\cba\\addons\\main\\script_macros.hpp", "#define A 1"),
+        reads: Cell::new(0),
+        lookups: Cell::new(0)
+    };
+    let mut sources: SourceDb = SourceDb::new();
+    let first = sources.open(&file_system, "\\x\\cba\\addons\\main\\script_macros.hpp").unwrap();
+    let main = sources.add("\\x\\cba\\addons\\main\\config.cpp", "");
+    let included = sources.include(&file_system, main, "script_macros.hpp").unwrap();
+    assert_eq!(first, included);
+    assert_eq!(file_system.reads.get(), 1);
+}
+
+#[test]
+fn includes_are_resolved_once() {
+    let file_system = CountingFileSystem {
+        inner: MemoryFileSystem::new().with_file("\\x\\cba\\addons\\main\\script_macros.hpp", "#define A 1"),
+        reads: Cell::new(0),
+        lookups: Cell::new(0)
+    };
+    let mut sources: SourceDb = SourceDb::new();
+    let main = sources.add("\\x\\cba\\addons\\main\\config.cpp", "");
+    let included = sources.include(&file_system, main, "script_macros.hpp").unwrap();
+    assert_eq!(sources[included].contents(), b"#define A 1");
+    assert_eq!((file_system.lookups.get(), file_system.reads.get()), (1, 1));
+    assert!(sources.include(&file_system, main, "missing.hpp").is_err());
+    assert_eq!((file_system.lookups.get(), file_system.reads.get()), (2, 1));
+}
+
+#[test]
+fn shared_includes_are_loaded_once_and_errors_name_their_file() {
+    let file_system = MemoryFileSystem::new()
+        .with_file("\\x\\cba\\addons\\main\\script_macros.hpp", "#define A 1\n")
+        .with_file("\\x\\cba\\addons\\main\\broken.hpp", "\n#endif\n");
+    let mut preprocessor = BohemiaPreProcessor::with_resolver(file_system);
+    for name in ["a.cpp", "b.cpp"] {
+        let main = preprocessor.sources_mut().add(&format!("\\x\\cba\\addons\\main\\{name}"), "#include \"script_macros.hpp\"\nA\n");
+        preprocessor.preprocess_file(main).unwrap();
+    }
+    assert_eq!(preprocessor.sources().len(), 3);
+
+    let main = preprocessor.sources_mut().add("\\x\\cba\\addons\\main\\c.cpp", "#include \"broken.hpp\"\n");
+    let error = preprocessor.preprocess_file(main).unwrap_err();
+    let broken = preprocessor.sources().file_id("\\x\\cba\\addons\\main\\broken.hpp").unwrap();
+    let span = error.span.unwrap();
+    assert_eq!((span.file, span.range.start), (broken, 1));
+}
+
+#[test]
+fn re_adding_a_file_replaces_its_name_and_lookups() {
+    let mut sources: SourceDb = SourceDb::new();
+    let first = sources.add("\\x\\cba\\addons\\main\\config.cpp", "a\nb");
+    let mut source_map = SourceMap::new();
+    source_map.add_file(&sources[first]);
+
+    let again = sources.add("/X/CBA/Addons/Main/Config.cpp", "ab");
+    assert_eq!(first, again);
+    assert_eq!(sources.iter().count(), 1);
+    assert_eq!(&**sources[first].name(), "/X/CBA/Addons/Main/Config.cpp");
+    assert_eq!(sources.file_id("x\\cba\\addons\\main\\config.cpp"), Some(first));
+    assert_eq!(sources.file_id("/X/CBA/ADDONS/MAIN/CONFIG.CPP"), Some(first));
+    assert_eq!(sources.file_id("/x/cba/addons/main/other.cpp"), None);
+
+    source_map.add_file(&sources[first]);
+    assert_eq!(source_map.file_name(first).map(|it| &**it), Some("/X/CBA/Addons/Main/Config.cpp"));
+    assert_eq!(source_map.line_col(&FileSpan::new(first, 1..2)), Some(LineCol { line: 0, column: 1 }));
+}
+
+#[test]
+fn sources_can_be_shared_across_threads() {
+    fn is_send_sync<T: Send + Sync>() {}
+    is_send_sync::<SourceDb>();
+    is_send_sync::<SourceMap>();
+    is_send_sync::<std::sync::Arc<SourceFile<u8>>>();
+
+    let mut db = SourceDb::new();
+    let id = db.add("a.hpp", b"one\ntwo".to_vec());
+    let file = db[id].clone();
+    let location = std::thread::spawn(move || file.line_col(4)).join().unwrap();
+    assert_eq!(location, Some(LineCol { line: 1, column: 0 }));
+}
@@ -0,0 +1,51 @@
+use bex::*;
+
+/// A map of `A B\nC` where `B` is an expansion of `OUTER` calling `INNER`, both written in `main.cpp`.
+fn fixture() -> (SourceDb, SourceMap, FileId) {
+    let mut sources: SourceDb = SourceDb::new();
+    let main = sources.add("main.cpp", "A OUTER\nC");
+    let mut map = SourceMap::new();
+    map.add_file(&sources[main]);
+    map.push(0..2, Origin::verbatim(FileSpan::new(main, 0..2)));
+    let call = FileSpan::new(main, 2..7);
+    map.push(2..3, Origin {
+        span: call.clone(),
+        expansions: vec![
+            Expansion { name: "OUTER".to_string(), call_site: call.clone() },
+            Expansion { name: "INNER".to_string(), call_site: FileSpan::new(main, 4..6) }
+        ]
+    });
+    map.push(3..5, Origin::verbatim(FileSpan::new(main, 7..9)));
+    (sources, map, main)
+}
+
+#[test]
+fn verbatim_offsets_resolve_mid_segment() {
+    let (_, map, main) = fixture();
+    let (origin, location) = map.resolve_location(4).unwrap();
+    assert_eq!(origin, Origin::verbatim(FileSpan::new(main, 8..9)));
+    assert_eq!(location, Some(LineCol { line: 1, column: 0 }));
+}
+
+#[test]
+fn expansions_resolve_to_the_outermost_call() {
+    let (_, map, main) = fixture();
+    let origin = map.resolve(2).unwrap();
+    assert_eq!(origin.span, FileSpan::new(main, 2..7));
+    let names: Vec<_> = origin.expansions.iter().map(|it| it.name.as_str()).collect();
+    assert_eq!(names, ["OUTER", "INNER"]);
+
+    let located = Located::resolve(BexError::UnexpectedEof { pos: 2 }, &map);
+    assert_eq!(located.file.as_deref(), Some("main.cpp"));
+    assert_eq!(located.call_sites[1].1, Some(LineCol { line: 0, column: 4 }));
+}
+
+#[test]
+fn the_end_of_the_output_resolves_to_the_end_of_the_file() {
+    let (_, map, main) = fixture();
+    assert_eq!(map.resolve(5), Some(Origin::verbatim(FileSpan::new(main, 9..9))));
+    assert_eq!(map.resolve(6), None);
+    let located = Located::resolve(BexError::UnexpectedEof { pos: 5 }, &map);
+    assert_eq!(located.file.as_deref(), Some("main.cpp"));
+    assert_eq!(located.location, Some(LineCol { line: 1, column: 1 }));
+}
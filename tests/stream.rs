@@ -0,0 +1,107 @@
+use std::io::{self, Read};
+use bex::*;
+
+/// Reader which never returns more than `max` bytes per call, like a socket or pipe
+struct ShortReads<R> {
+    inner: R,
+    max:   usize
+}
+
+impl<R: Read> Read for ShortReads<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.max);
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+fn short_reads(bytes: &[u8], max: usize) -> ShortReads<&[u8]> {
+    ShortReads { inner: bytes, max }
+}
+
+#[test]
+fn rewinding_past_the_window_is_an_error() {
+    let source: Vec<u8> = (0..64).collect();
+    let mut analyser = ReaderAnalyser::with_capacity(short_reads(&source, 3), 4, 2).unwrap();
+    for _ in 0..20 {
+        analyser.step_forward().unwrap();
+    }
+    let window_start = analyser.window_start();
+    assert!(window_start > 0);
+
+    let error = analyser.set_pos(0).unwrap_err();
+    assert!(matches!(error, BexError::RewindPastWindow { pos: 0, window_start: start } if start == window_start));
+    assert_eq!(analyser.pos(), 20);
+
+    analyser.set_pos(18).unwrap();
+    assert_eq!(analyser.get().unwrap(), 18);
+}
+
+#[test]
+fn reads_every_byte_across_window_boundaries() {
+    let source: Vec<u8> = (0..=255).cycle().take(1000).collect();
+    let mut analyser = ReaderAnalyser::with_capacity(short_reads(&source, 3), 4, 2).unwrap();
+    let mut read = vec![];
+    while !analyser.is_end() {
+        read.push(analyser.get().unwrap());
+    }
+    assert_eq!(read, source);
+    assert!(matches!(analyser.get(), Err(BexError::UnexpectedEof { pos: 1000 })));
+}
+
+#[test]
+fn seek_until_spans_refills() {
+    let mut source = vec![b'a'; 100];
+    source.push(b';');
+    source.extend_from_slice(b"rest");
+    let mut analyser = ReaderAnalyser::with_capacity(short_reads(&source, 3), 8, 4).unwrap();
+
+    analyser.seek_until(b';').unwrap();
+    assert_eq!(analyser.pos(), 100);
+    assert!(analyser.window_start() > 0);
+
+    let checkpoint = analyser.checkpoint();
+    analyser.step_forward().unwrap();
+    assert_eq!(analyser.get().unwrap(), b'r');
+    analyser.rollback(checkpoint).unwrap();
+    assert_eq!(analyser.get().unwrap(), b';');
+
+    assert!(matches!(analyser.seek_until(b';'), Err(BexError::UnexpectedEof { pos: 105 })));
+}
+
+#[test]
+fn large_readers_use_constant_memory() {
+    const LEN: u64 = 1 << 20;
+    let (chunk_size, lookbehind) = (256, 64);
+    let reader = ShortReads { inner: io::repeat(b'x').take(LEN), max: 100 };
+    let mut analyser = ReaderAnalyser::with_capacity(reader, chunk_size, lookbehind).unwrap();
+
+    let mut largest = 0;
+    while !analyser.is_end() {
+        analyser.step_forward().unwrap();
+        largest = largest.max(analyser.window().1.len());
+    }
+    assert_eq!(analyser.pos() as u64, LEN);
+    assert!(largest <= chunk_size + lookbehind, "window grew to {largest} bytes");
+}
+
+#[test]
+fn skipping_far_ahead_keeps_the_window_bounded() {
+    const LEN: usize = 1 << 20;
+    let (chunk_size, lookbehind) = (256, 64);
+    let source: Vec<u8> = (0..=255).cycle().take(LEN).collect();
+    let mut analyser = ReaderAnalyser::with_capacity(short_reads(&source, 100), chunk_size, lookbehind).unwrap();
+
+    analyser.set_pos(500_000).unwrap();
+    assert!(analyser.window().1.len() <= chunk_size + lookbehind, "window grew to {} bytes", analyser.window().1.len());
+    assert_eq!(analyser.get().unwrap(), source[500_000]);
+    analyser.set_pos(500_000 - lookbehind).unwrap();
+    assert_eq!(analyser.get().unwrap(), source[500_000 - lookbehind]);
+
+    analyser.advance(100_000).unwrap();
+    assert!(analyser.window().1.len() <= chunk_size + lookbehind);
+    assert_eq!(analyser.pos(), 600_000 - lookbehind + 1);
+
+    assert!(matches!(analyser.set_pos(LEN + 10), Err(BexError::CursorOutOfBounds { pos, len: LEN }) if pos == LEN + 10));
+    assert_eq!(analyser.pos(), analyser.window_start());
+    assert_eq!(analyser.get().unwrap(), source[analyser.pos() - 1]);
+}
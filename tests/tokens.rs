@@ -0,0 +1,74 @@
+use bex::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tok {
+    Digit(u8),
+    Comma
+}
+
+impl Token<'_, u8> for Tok {
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'_, u8>) -> Result<Self> {
+        let start = lexer.pos();
+        match lexer.get()? {
+            b',' => Ok(Tok::Comma),
+            it if it.is_ascii_digit() => Ok(Tok::Digit(it - b'0')),
+            it => Err(BexError::unexpected(&(it as char), "a digit or `,`", start..start + 1))
+        }
+    }
+}
+
+fn peeked(stream: &mut TokenStream<Tok, u8>, n: usize) -> Option<Tok> {
+    stream.peek_nth(n).map(|it| *it.as_ref().unwrap())
+}
+
+#[test]
+fn peek_nth_buffers_without_consuming() {
+    let mut stream = Lexer::from_slice(b"1,2").token_stream::<Tok>();
+    assert_eq!(peeked(&mut stream, 2), Some(Tok::Digit(2)));
+    assert_eq!(stream.lexer().pos(), 3);
+    assert_eq!(peeked(&mut stream, 0), Some(Tok::Digit(1)));
+    assert_eq!(peeked(&mut stream, 3), None);
+
+    assert!(matches!(stream.next(), Some(Ok(Tok::Digit(1)))));
+    assert_eq!(peeked(&mut stream, 0), Some(Tok::Comma));
+    assert_eq!(peeked(&mut stream, 1), Some(Tok::Digit(2)));
+    assert_eq!(stream.by_ref().count(), 2);
+    assert!(stream.peek().is_none());
+}
+
+#[test]
+fn the_stream_ends_after_the_first_error() {
+    let mut stream = Lexer::from_slice(b"1?2").token_stream::<Tok>();
+    assert!(matches!(stream.peek_nth(1), Some(Err(BexError::Unexpected { .. }))));
+    assert!(stream.peek_nth(2).is_none());
+    assert!(matches!(stream.next(), Some(Ok(Tok::Digit(1)))));
+    assert!(matches!(stream.next(), Some(Err(_))));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn into_lexer_moves_back_to_the_first_unconsumed_token() {
+    let mut stream = Lexer::from_slice(b"1,2,3").token_stream::<Tok>();
+    stream.next();
+    assert_eq!(peeked(&mut stream, 2), Some(Tok::Comma));
+    let lexer = stream.into_lexer();
+    assert_eq!(lexer.pos(), 1);
+    assert_eq!(lexer.tokenize_until_end::<Tok>().unwrap(), [Tok::Comma, Tok::Digit(2), Tok::Comma, Tok::Digit(3)]);
+
+    let mut stream = Lexer::from_slice(b"1,2").token_stream::<Tok>();
+    stream.next();
+    stream.next();
+    assert_eq!(stream.into_lexer().pos(), 2);
+}
+
+#[test]
+fn streams_start_at_the_cursor() {
+    let mut lexer = Lexer::from_slice(b"1,2");
+    lexer.set_pos(2).unwrap();
+    let mut stream = lexer.token_stream::<Tok>();
+    assert!(matches!(stream.next(), Some(Ok(Tok::Digit(2)))));
+    assert!(stream.next().is_none());
+    assert!(stream.into_lexer().is_end());
+}
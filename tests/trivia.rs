@@ -0,0 +1,78 @@
+use bex::*;
+use proptest::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Word,
+    Punct(u8)
+}
+
+impl Token<'_, u8> for Tok {
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'_, u8>) -> Result<Self> {
+        match lexer.get()? {
+            it if it.is_ascii_alphanumeric() => {
+                lexer.skip_while(u8::is_ascii_alphanumeric)?;
+                Ok(Tok::Word)
+            }
+            it => Ok(Tok::Punct(it))
+        }
+    }
+}
+
+impl TriviaToken<'_, u8> for Tok {
+    fn next_trivia(lexer: &mut Lexer<'_, u8>) -> Result<Option<TriviaKind>> { c_style_trivia(lexer) }
+}
+
+#[test]
+fn trivia_is_attached_to_tokens() {
+    let source = b"// header\r\nclass A { /* doc */ x = 1; // one\n};\n";
+    let tokens = Lexer::from_slice(source).tokenize_lossless::<Tok>().unwrap();
+    let first = &tokens.tokens()[0];
+    let kinds: Vec<_> = first.leading.iter().map(|it| it.kind).collect();
+    assert_eq!(kinds, [TriviaKind::LineComment, TriviaKind::Newline]);
+    assert_eq!(tokens.text(&first.leading[0].span), b"// header");
+
+    let semicolon = tokens.tokens().iter().find(|it| it.token == Tok::Punct(b';')).unwrap();
+    let kinds: Vec<_> = semicolon.trailing.iter().map(|it| it.kind).collect();
+    assert_eq!(kinds, [TriviaKind::Whitespace, TriviaKind::LineComment]);
+
+    let brace = &tokens.tokens()[2];
+    assert_eq!(tokens.text(&brace.trailing[1].span), b"/* doc */");
+    assert_eq!(tokens.end_trivia().iter().map(|it| it.kind).collect::<Vec<_>>(), [TriviaKind::Newline]);
+    assert_eq!(tokens.to_source(), source);
+}
+
+proptest! {
+    #[test]
+    fn to_source_reproduces_the_input(source in "([a-z0-9]{1,4}|[ \t]|\r?\n|//[a-z ]*|/\\*[a-z \n*/]*(\\*/)?|[{};=/*])*") {
+        let tokens = Lexer::from_vec(source.clone().into_bytes()).tokenize_lossless::<Tok>().unwrap();
+        prop_assert_eq!(tokens.to_source(), source.into_bytes());
+    }
+}
+
+/// Claims trivia everywhere without reading any, which must not stall the lexer
+#[derive(Debug, PartialEq)]
+struct ZeroWidth(u8);
+
+impl Token<'_, u8> for ZeroWidth {
+    type Error = BexError;
+
+    fn next_token(lexer: &mut Lexer<'_, u8>) -> Result<Self> { Ok(ZeroWidth(lexer.get()?)) }
+}
+
+impl TriviaToken<'_, u8> for ZeroWidth {
+    fn next_trivia(lexer: &mut Lexer<'_, u8>) -> Result<Option<TriviaKind>> {
+        Ok(c_style_trivia(lexer)?.or(Some(TriviaKind::Whitespace)))
+    }
+}
+
+#[test]
+fn trivia_which_reads_nothing_starts_a_token() {
+    let tokens = Lexer::from_slice(b"a b\nc").tokenize_lossless::<ZeroWidth>().unwrap();
+    let read: Vec<_> = tokens.tokens().iter().map(|it| (it.token.0, it.span.clone(), it.trailing.len())).collect();
+    assert_eq!(read, [(b'a', 0..1, 1), (b'b', 2..3, 0), (b'c', 4..5, 0)]);
+    assert_eq!(tokens.tokens()[2].leading.iter().map(|it| it.kind).collect::<Vec<_>>(), [TriviaKind::Newline]);
+    assert_eq!(tokens.to_source(), b"a b\nc");
+}
@@ -0,0 +1,33 @@
+use bex::*;
+
+#[test]
+fn characters_keep_byte_offsets() {
+    let mut lexer = Lexer::from_slice("név = \"árvíztűrő\";".as_bytes());
+    assert_eq!(lexer.get_char().unwrap(), 'n');
+    assert_eq!(lexer.peek_char().unwrap(), 'é');
+    assert!(!lexer.take_char('e').unwrap());
+    assert!(lexer.take_char('é').unwrap());
+    assert_eq!(lexer.pos(), 3);
+    lexer.seek_byte(b'"').unwrap();
+    lexer.step_forward().unwrap();
+    let start = lexer.pos();
+    assert_eq!(lexer.skip_chars_while(char::is_alphabetic).unwrap(), "árvíztűrő".len());
+    assert_eq!(&lexer.contents()[start..lexer.pos()], "árvíztűrő".as_bytes());
+    assert!(lexer.take(&b'"').unwrap());
+}
+
+#[test]
+fn invalid_sequences_report_their_position() {
+    let mut lexer = Lexer::from_slice(b"ab\xC3\x28c");
+    lexer.advance(2).unwrap();
+    let error = lexer.get_char().unwrap_err();
+    assert!(matches!(error, BexError::InvalidUtf8 { span } if span == (2..3)));
+    assert_eq!(lexer.pos(), 2);
+
+    let mut lexer = Lexer::from_slice(b"a\xE2\x82");
+    assert_eq!(lexer.skip_chars_while(|_| true).unwrap_err().span(), Some(1..3));
+    assert_eq!(lexer.pos(), 1);
+
+    lexer.advance(2).unwrap();
+    assert!(matches!(lexer.peek_char(), Err(BexError::UnexpectedEof { pos: 3 })));
+}
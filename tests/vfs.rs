@@ -0,0 +1,54 @@
+use std::fs;
+use bex::*;
+
+#[test]
+fn paths_are_normalized() {
+    assert_eq!(normalize_path("x\\cba\\addons\\..\\main.hpp"), "/x/cba/main.hpp");
+    assert_eq!(normalize_path("/a/./b//c"), "/a/b/c");
+}
+
+#[test]
+fn includes_resolve_relative_to_the_including_file() {
+    let file_system = MemoryFileSystem::new()
+        .with_file("\\x\\cba\\addons\\main\\script_component.hpp", "a")
+        .with_file("\\x\\cba\\addons\\common\\script_component.hpp", "b");
+    let (path, contents) = file_system.load("\\x\\cba\\addons\\main\\config.cpp", "script_component.hpp").unwrap();
+    assert_eq!((path.as_str(), &contents[..]), ("/x/cba/addons/main/script_component.hpp", &b"a"[..]));
+    let (_, contents) = file_system.load("\\x\\cba\\addons\\main\\config.cpp", "..\\common\\script_component.hpp").unwrap();
+    assert_eq!(contents, b"b");
+    assert!(file_system.load("\\x\\cba\\addons\\main\\config.cpp", "missing.hpp").is_err());
+}
+
+#[test]
+fn layers_fall_back_to_disk_prefix_mappings() {
+    let directory = std::env::temp_dir().join(format!("bex-vfs-{}", std::process::id()));
+    fs::create_dir_all(directory.join("addons/main")).unwrap();
+    fs::write(directory.join("addons/main/script_macros.hpp"), "disk").unwrap();
+
+    let file_system = LayeredFileSystem::new()
+        .with_layer(MemoryFileSystem::new().with_file("\\x\\cba\\addons\\main\\override.hpp", "memory"))
+        .with_layer(DiskFileSystem::new().with_prefix("\\x\\cba", &directory));
+    assert_eq!(file_system.read("/x/cba/addons/main/override.hpp").unwrap(), b"memory");
+    assert_eq!(file_system.read("/x/cba/addons/main/script_macros.hpp").unwrap(), b"disk");
+    assert!(!file_system.exists("/x/ace/addons/main/script_macros.hpp"));
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn disk_paths_are_matched_case_insensitively() {
+    let directory = std::env::temp_dir().join(format!("bex-vfs-case-{}", std::process::id()));
+    fs::create_dir_all(directory.join("Addons/Main")).unwrap();
+    fs::write(directory.join("Addons/Main/Script_Macros.hpp"), "disk").unwrap();
+
+    let file_system = DiskFileSystem::new().with_prefix("\\X\\CBA", &directory);
+    assert_eq!(file_system.read("/x/cba/addons/main/script_macros.hpp").unwrap(), b"disk");
+    assert_eq!(file_system.find("/x/cba/ADDONS/main/SCRIPT_MACROS.HPP"), Some(directory.join("Addons/Main/Script_Macros.hpp")));
+    assert!(file_system.exists("/X/Cba/Addons/Main/script_macros.hpp"));
+    assert!(!file_system.exists("/x/cba/addons/main"));
+    assert!(!file_system.exists("/x/cba/addons/other/script_macros.hpp"));
+    let (path, _) = file_system.load("/x/cba/addons/main/config.cpp", "SCRIPT_macros.hpp").unwrap();
+    assert_eq!(path, "/x/cba/addons/main/SCRIPT_macros.hpp");
+
+    fs::remove_dir_all(directory).unwrap();
+}